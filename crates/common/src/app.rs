@@ -6,36 +6,233 @@
 
 //! Apps and their recent items.
 
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 mod item;
+mod itemid;
 mod launchservice;
 mod provider;
+mod running;
+mod scopes;
+mod snapshot;
 
-pub use item::AppLaunchItem;
-pub use launchservice::{App, AppId, AppLaunchClient, AppLaunchService, SystemdScopeSettings};
+pub use item::{AppLaunchItem, RelativeItem};
+pub use itemid::{AppItemId, AppItemIdParseError};
+pub use launchservice::{
+    App, AppId, AppLaunchClient, AppLaunchService, LaunchOptions, PendingLaunches, ScopeMode,
+    SystemdScopeSettings,
+};
 pub use provider::AppItemSearchProvider;
+pub use running::{RunningInstance, RunningInstanceError};
+pub use scopes::{LaunchedScope, LaunchedScopes};
+
+pub use crate::systemd::ScopeResources;
 
 #[cfg(test)]
 mod tests {
+    mod item_id {
+        use pretty_assertions::assert_eq;
+
+        use crate::app::{AppItemId, AppItemIdParseError};
+
+        #[test]
+        fn roundtrip() {
+            let id = AppItemId::new(
+                "jetbrains-idea.desktop".into(),
+                "/home/foo/dev/mdcat".to_string(),
+            );
+            assert_eq!(id.to_string().parse::<AppItemId>(), Ok(id));
+        }
+
+        #[test]
+        fn roundtrip_odd_characters() {
+            let id = AppItemId::new(
+                "jetbrains-idea.desktop".into(),
+                "/home/foo/dev/a:b c/%20/Müller/プロジェクト".to_string(),
+            );
+            let s = id.to_string();
+            assert!(
+                s.chars().all(|c| c.is_ascii_graphic()),
+                "Unexpected characters in {}",
+                s
+            );
+            assert_eq!(s.parse::<AppItemId>(), Ok(id));
+        }
+
+        #[test]
+        fn format() {
+            let id = AppItemId::new(
+                "jetbrains-idea.desktop".into(),
+                "/home/foo/dev/mdcat".to_string(),
+            );
+            assert_eq!(
+                id.to_string(),
+                "app-item:v1:jetbrains-idea.desktop:%2Fhome%2Ffoo%2Fdev%2Fmdcat"
+            );
+        }
+
+        #[test]
+        fn parse_invalid() {
+            assert_eq!(
+                "jetbrains-recent-project-foo.desktop-/home/foo".parse::<AppItemId>(),
+                Err(AppItemIdParseError::InvalidPrefix)
+            );
+            assert_eq!(
+                "app-item:v0:foo.desktop:%2Fhome".parse::<AppItemId>(),
+                Err(AppItemIdParseError::UnsupportedVersion)
+            );
+            assert_eq!(
+                "app-item:v1:foo.desktop".parse::<AppItemId>(),
+                Err(AppItemIdParseError::InvalidComponent)
+            );
+            assert_eq!(
+                "app-item:v1:foo.desktop:%2Fhome:extra".parse::<AppItemId>(),
+                Err(AppItemIdParseError::InvalidComponent)
+            );
+        }
+    }
+
+    mod activate {
+        use pretty_assertions::assert_eq;
+
+        use crate::app::provider::resolve_item_uri;
+        use crate::app::{AppId, AppItemId, AppLaunchItem};
+        use crate::source::IdMap;
+
+        fn items(uris: &[&str]) -> IdMap<AppLaunchItem> {
+            uris.iter()
+                .map(|uri| {
+                    (
+                        AppItemId::new("foo.desktop".into(), uri.to_string()).to_string(),
+                        AppLaunchItem::new("foo".to_string(), uri.to_string(), Vec::new()),
+                    )
+                })
+                .collect()
+        }
+
+        #[test]
+        fn resolve_known_item() {
+            let app = AppId::from("foo.desktop");
+            let items = items(&["/home/foo/dev/mdcat"]);
+            let id = items.keys().next().unwrap().clone();
+            assert_eq!(
+                resolve_item_uri(&items, &app, &id),
+                Some("/home/foo/dev/mdcat".to_string())
+            );
+        }
+
+        #[test]
+        fn resolve_item_after_refresh() {
+            let app = AppId::from("foo.desktop");
+            let before_search = items(&["/home/foo/dev/mdcat", "/home/foo/dev/bar"]);
+            let id = before_search.keys().next().unwrap().clone();
+            // The items got refreshed after the search, and no longer contain the item
+            let after_refresh = items(&["/home/foo/dev/bar"]);
+            assert_eq!(
+                resolve_item_uri(&after_refresh, &app, &id),
+                Some("/home/foo/dev/mdcat".to_string())
+            );
+            // The service restarted and didn't load any items yet
+            assert_eq!(
+                resolve_item_uri(&IdMap::new(), &app, &id),
+                Some("/home/foo/dev/mdcat".to_string())
+            );
+        }
+
+        #[test]
+        fn do_not_resolve_item_of_other_app() {
+            let app = AppId::from("bar.desktop");
+            let id = AppItemId::new("foo.desktop".into(), "/home/foo/dev/mdcat".to_string());
+            assert_eq!(resolve_item_uri(&IdMap::new(), &app, &id.to_string()), None);
+        }
+
+        #[test]
+        fn do_not_resolve_invalid_id() {
+            let app = AppId::from("foo.desktop");
+            assert_eq!(
+                resolve_item_uri(&IdMap::new(), &app, "jetbrains-recent-project-foo"),
+                None
+            );
+        }
+    }
+
+    mod snapshot {
+        use pretty_assertions::assert_eq;
+
+        use crate::app::snapshot::SharedItems;
+        use crate::source::IdMap;
+
+        fn items(ids: &[&str]) -> IdMap<()> {
+            ids.iter().map(|id| (id.to_string(), ())).collect()
+        }
+
+        #[test]
+        fn update_replaces_older_snapshot() {
+            let shared = SharedItems::default();
+            let version = shared.next_version();
+            shared.update(version, items(&["foo"]));
+            assert_eq!(shared.current().version, version);
+            assert_eq!(shared.current().items, items(&["foo"]));
+        }
+
+        #[test]
+        fn update_keeps_newer_snapshot() {
+            let shared = SharedItems::default();
+            let first = shared.next_version();
+            let second = shared.next_version();
+            shared.update(second, items(&["second"]));
+            let snapshot = shared.update(first, items(&["first"]));
+            // The caller still sees the items it loaded…
+            assert_eq!(snapshot.items, items(&["first"]));
+            // …but the newer snapshot stays current
+            assert_eq!(shared.current().version, second);
+            assert_eq!(shared.current().items, items(&["second"]));
+        }
+
+        #[test]
+        fn readers_keep_their_snapshot() {
+            let shared = SharedItems::default();
+            let version = shared.next_version();
+            shared.update(version, items(&["foo"]));
+            let reader = shared.current();
+            let version = shared.next_version();
+            shared.clone().update(version, items(&["bar"]));
+            assert_eq!(reader.items, items(&["foo"]));
+            assert_eq!(shared.current().items, items(&["bar"]));
+        }
+    }
+
     mod search {
         use pretty_assertions::assert_eq;
 
         use crate::app::AppLaunchItem;
-        use crate::matching::find_matching_items;
+        use crate::history::tests::{activation, history};
+        use crate::matching::{
+            find_matching_items, find_matching_items_with_boost, normalize, Query, ScoreMatchable,
+        };
+
+        /// The roots to match paths relative to.
+        ///
+        /// Tests use these instead of the home directory of the user running the tests, so that
+        /// rankings don't depend on `$HOME`.
+        const ROOTS: &[&str] = &["/home/foo"];
 
         fn do_match<'a>(items: &[(&'a str, AppLaunchItem)], terms: &[&str]) -> Vec<&'a str> {
-            find_matching_items(items.iter().map(|(s, p)| (*s, p)), terms)
+            find_matching_items(
+                items.iter().map(|(s, p)| (*s, p.relative_to(ROOTS))),
+                &Query::parse(terms),
+            )
         }
 
         #[test]
         fn matches_something() {
             let items = vec![(
                 "foo",
-                AppLaunchItem {
-                    name: "mdcat".to_string(),
-                    uri: "/home/foo/dev/mdcat".to_string(),
-                },
+                AppLaunchItem::new(
+                    "mdcat".to_string(),
+                    "/home/foo/dev/mdcat".to_string(),
+                    Vec::new(),
+                ),
             )];
             assert_eq!(do_match(&items, &["mdcat"]), ["foo"]);
         }
@@ -46,24 +243,27 @@ mod tests {
             let items = vec![
                 (
                     "foo-1",
-                    AppLaunchItem {
-                        name: "ui-pattern-library".to_string(),
-                        uri: "/home/foo/dev/something/ui-pattern-library".to_string(),
-                    },
+                    AppLaunchItem::new(
+                        "ui-pattern-library".to_string(),
+                        "/home/foo/dev/something/ui-pattern-library".to_string(),
+                        Vec::new(),
+                    ),
                 ),
                 (
                     "foo-2",
-                    AppLaunchItem {
-                        name: "dauntless-builder".to_string(),
-                        uri: "/home/foo/dev/dauntless-builder".to_string(),
-                    },
+                    AppLaunchItem::new(
+                        "dauntless-builder".to_string(),
+                        "/home/foo/dev/dauntless-builder".to_string(),
+                        Vec::new(),
+                    ),
                 ),
                 (
                     "foo-3",
-                    AppLaunchItem {
-                        name: "typo3-ssr".to_string(),
-                        uri: "/home/foo/dev/something/typo3-ssr".to_string(),
-                    },
+                    AppLaunchItem::new(
+                        "typo3-ssr".to_string(),
+                        "/home/foo/dev/something/typo3-ssr".to_string(),
+                        Vec::new(),
+                    ),
                 ),
             ];
             assert!(do_match(&items, &["flutter_test_app"]).is_empty());
@@ -73,10 +273,11 @@ mod tests {
         fn ignore_case_of_name() {
             let items = vec![(
                 "foo",
-                AppLaunchItem {
-                    name: "mdCat".to_string(),
-                    uri: "/home/foo/dev/foo".to_string(),
-                },
+                AppLaunchItem::new(
+                    "mdCat".to_string(),
+                    "/home/foo/dev/foo".to_string(),
+                    Vec::new(),
+                ),
             )];
             assert_eq!(do_match(&items, &["Mdcat"]), ["foo"]);
         }
@@ -85,10 +286,11 @@ mod tests {
         fn ignore_case_of_path() {
             let items = vec![(
                 "foo",
-                AppLaunchItem {
-                    name: "bar".to_string(),
-                    uri: "/home/foo/dev/mdcaT".to_string(),
-                },
+                AppLaunchItem::new(
+                    "bar".to_string(),
+                    "/home/foo/dev/mdcaT".to_string(),
+                    Vec::new(),
+                ),
             )];
             assert_eq!(do_match(&items, &["Mdcat"]), ["foo"]);
         }
@@ -98,43 +300,443 @@ mod tests {
             let items = vec![
                 (
                     "1",
-                    AppLaunchItem {
-                        name: "bar".to_string(),
-                        // This matches foo as well because of /home/foo
-                        uri: "/home/foo/dev/bar".to_string(),
-                    },
+                    AppLaunchItem::new(
+                        "bar".to_string(),
+                        // This matches foo in the path, but not in the name
+                        "/home/foo/foo/bar".to_string(),
+                        Vec::new(),
+                    ),
                 ),
                 (
                     "2",
-                    AppLaunchItem {
-                        name: "foo".to_string(),
-                        uri: "/home/foo/dev/foo".to_string(),
-                    },
+                    AppLaunchItem::new(
+                        "foo".to_string(),
+                        "/home/foo/dev/foo".to_string(),
+                        Vec::new(),
+                    ),
                 ),
             ];
             assert_eq!(do_match(&items, &["foo"]), ["2", "1"]);
         }
 
+        #[test]
+        fn matches_directory_name_like_name() {
+            let items = vec![
+                (
+                    "1",
+                    AppLaunchItem::new(
+                        "Service".to_string(),
+                        "/home/foo/dev/acme-svc/service".to_string(),
+                        Vec::new(),
+                    ),
+                ),
+                (
+                    "2",
+                    AppLaunchItem::new(
+                        "Acme Service".to_string(),
+                        "/home/foo/dev/acme-svc".to_string(),
+                        Vec::new(),
+                    ),
+                ),
+            ];
+            assert_eq!(do_match(&items, &["acme-svc"]), ["2", "1"]);
+        }
+
+        #[test]
+        fn matches_keywords() {
+            let items = vec![
+                (
+                    "1",
+                    AppLaunchItem::new(
+                        "service".to_string(),
+                        "/home/foo/dev/service".to_string(),
+                        vec!["gradle".to_string(), "java".to_string(), "acme".to_string()],
+                    ),
+                ),
+                (
+                    "2",
+                    AppLaunchItem::new(
+                        "tool".to_string(),
+                        "/home/foo/dev/tool".to_string(),
+                        vec!["cargo".to_string(), "acme".to_string()],
+                    ),
+                ),
+                (
+                    "3",
+                    AppLaunchItem::new(
+                        "other".to_string(),
+                        "/home/foo/dev/other".to_string(),
+                        vec!["gradle".to_string(), "initech".to_string()],
+                    ),
+                ),
+            ];
+            assert_eq!(do_match(&items, &["acme", "gradle"]), ["1"]);
+            assert_eq!(do_match(&items, &["acme", "tool"]), ["2"]);
+        }
+
+        #[test]
+        fn matches_in_keywords_rank_lower() {
+            let items = vec![
+                (
+                    "1",
+                    AppLaunchItem::new(
+                        "service".to_string(),
+                        "/home/foo/dev/service".to_string(),
+                        vec!["gradle".to_string()],
+                    ),
+                ),
+                (
+                    "2",
+                    AppLaunchItem::new(
+                        "gradle-plugin".to_string(),
+                        "/home/foo/dev/plugin".to_string(),
+                        Vec::new(),
+                    ),
+                ),
+            ];
+            assert_eq!(do_match(&items, &["gradle"]), ["2", "1"]);
+        }
+
+        fn qualified_items() -> Vec<(&'static str, AppLaunchItem)> {
+            vec![
+                (
+                    "1",
+                    AppLaunchItem::new(
+                        "foo".to_string(),
+                        "/home/user/work/foo".to_string(),
+                        Vec::new(),
+                    ),
+                ),
+                (
+                    "2",
+                    AppLaunchItem::new(
+                        "foo".to_string(),
+                        "/home/user/archive/foo".to_string(),
+                        Vec::new(),
+                    ),
+                ),
+                (
+                    "3",
+                    AppLaunchItem::new(
+                        "bar".to_string(),
+                        "/home/user/work/foo-bar".to_string(),
+                        Vec::new(),
+                    ),
+                ),
+            ]
+        }
+
+        #[test]
+        fn matches_name_qualifier() {
+            let items = qualified_items();
+            assert_eq!(do_match(&items, &["name:bar"]), ["3"]);
+            assert_eq!(do_match(&items, &["name:work"]), Vec::<&str>::new());
+        }
+
+        #[test]
+        fn matches_path_qualifier() {
+            let items = qualified_items();
+            assert_eq!(do_match(&items, &["foo", "path:work/"]), ["1", "3"]);
+        }
+
+        #[test]
+        fn excludes_negated_terms() {
+            let items = qualified_items();
+            assert_eq!(do_match(&items, &["foo", "-archive"]), ["1", "3"]);
+            // The match in 2 is further to the right of the path
+            assert_eq!(do_match(&items, &["foo", "-name:bar"]), ["2", "1"]);
+        }
+
+        #[test]
+        fn matches_only_negated_terms() {
+            let items = qualified_items();
+            assert_eq!(do_match(&items, &["-archive"]), ["1", "3"]);
+        }
+
+        #[test]
+        fn ignores_app_qualifier() {
+            let items = qualified_items();
+            assert_eq!(do_match(&items, &["ide:rider", "name:bar"]), ["3"]);
+        }
+
+        #[test]
+        fn unknown_qualifier_is_plain_term() {
+            let items = vec![(
+                "1",
+                AppLaunchItem::new(
+                    "std:fmt".to_string(),
+                    "/home/user/work/fmt".to_string(),
+                    Vec::new(),
+                ),
+            )];
+            assert_eq!(do_match(&items, &["std:fmt"]), ["1"]);
+        }
+
+        #[test]
+        fn does_not_match_home_prefix() {
+            let items = vec![(
+                "1",
+                AppLaunchItem::new(
+                    "bar".to_string(),
+                    "/home/foo/dev/bar".to_string(),
+                    Vec::new(),
+                ),
+            )];
+            assert!(do_match(&items, &["foo"]).is_empty());
+            assert!(do_match(&items, &["home"]).is_empty());
+            assert!(do_match(&items, &["path:foo"]).is_empty());
+            assert_eq!(do_match(&items, &["dev"]), ["1"]);
+        }
+
+        #[test]
+        fn does_not_match_home_directory_by_default() {
+            let home = crate::paths::home_dir();
+            let item =
+                AppLaunchItem::new("bar".to_string(), format!("{}/dev/bar", home), Vec::new());
+            assert_eq!(item.match_score(&Query::parse(&["home"])), 0.0);
+            assert!(0.0 < item.match_score(&Query::parse(&["dev"])));
+        }
+
+        #[test]
+        fn does_not_match_root_prefixes() {
+            let items = vec![
+                (
+                    "1",
+                    AppLaunchItem::new(
+                        "mdcat".to_string(),
+                        "/home/foo/Code/gh/mdcat".to_string(),
+                        Vec::new(),
+                    ),
+                ),
+                (
+                    "2",
+                    AppLaunchItem::new(
+                        "pages".to_string(),
+                        "/home/foo/Code/gitlab/gh-pages".to_string(),
+                        Vec::new(),
+                    ),
+                ),
+            ];
+            // Providers normalize their roots, like the paths of items
+            let roots: Vec<String> = ["/home/foo", "/home/foo/Code/gh"]
+                .iter()
+                .map(|root| normalize(root))
+                .collect();
+            let matches = find_matching_items(
+                items
+                    .iter()
+                    .map(|(id, item)| (*id, item.relative_to(&roots))),
+                &Query::parse(&["gh"]),
+            );
+            assert_eq!(matches, ["2"]);
+        }
+
         #[test]
         fn matches_at_end_of_path_rank_higher() {
             let items = vec![
                 (
                     "1",
-                    AppLaunchItem {
-                        name: "p1".to_string(),
-                        // This matches foo as well because of /home/foo
-                        uri: "/home/foo/dev/bar".to_string(),
-                    },
+                    AppLaunchItem::new(
+                        "p1".to_string(),
+                        // This matches foo at the start of the path below /home/foo
+                        "/home/foo/foo/bar".to_string(),
+                        Vec::new(),
+                    ),
                 ),
                 (
                     "2",
-                    AppLaunchItem {
-                        name: "p1".to_string(),
-                        uri: "/home/foo/dev/foo".to_string(),
-                    },
+                    AppLaunchItem::new(
+                        "p1".to_string(),
+                        "/home/foo/dev/foo".to_string(),
+                        Vec::new(),
+                    ),
                 ),
             ];
             assert_eq!(do_match(&items, &["foo"]), ["2", "1"]);
         }
+
+        #[test]
+        fn exact_final_component_ranks_higher_than_long_parent() {
+            let items = vec![
+                (
+                    "1",
+                    AppLaunchItem::new(
+                        "api web".to_string(),
+                        "/srv/projects-containing-api/web".to_string(),
+                        Vec::new(),
+                    ),
+                ),
+                (
+                    "2",
+                    AppLaunchItem::new("api".to_string(), "/srv/dev/api".to_string(), Vec::new()),
+                ),
+            ];
+            assert_eq!(do_match(&items, &["api"]), ["2", "1"]);
+        }
+
+        #[test]
+        fn ignore_diacritics() {
+            let items = vec![(
+                "1",
+                AppLaunchItem::new(
+                    "Müller".to_string(),
+                    "/home/foo/dev/Kunden/Müller".to_string(),
+                    Vec::new(),
+                ),
+            )];
+            assert_eq!(do_match(&items, &["muller"]), ["1"]);
+            assert_eq!(do_match(&items, &["MÜLLER"]), ["1"]);
+            assert_eq!(do_match(&items, &["path:kunden/mul"]), ["1"]);
+        }
+
+        #[test]
+        fn fold_case_of_sharp_s() {
+            let items = vec![(
+                "1",
+                AppLaunchItem::new(
+                    "Straßenbahn".to_string(),
+                    "/home/foo/dev/Straßenbahn".to_string(),
+                    Vec::new(),
+                ),
+            )];
+            assert_eq!(do_match(&items, &["strasse"]), ["1"]);
+            assert_eq!(do_match(&items, &["STRASSE"]), ["1"]);
+        }
+
+        #[test]
+        fn matches_japanese_paths() {
+            let items = vec![(
+                "1",
+                AppLaunchItem::new(
+                    "ウェブサイト".to_string(),
+                    "/home/foo/開発/ウェブサイト".to_string(),
+                    Vec::new(),
+                ),
+            )];
+            assert_eq!(do_match(&items, &["サイト"]), ["1"]);
+            assert_eq!(do_match(&items, &["開発"]), ["1"]);
+        }
+
+        #[test]
+        fn score_positions_in_characters() {
+            // Both paths have the same number of characters before the match, but the
+            // Japanese path has many more bytes; scoring by bytes would rank it higher.
+            let items = vec![
+                (
+                    "1",
+                    AppLaunchItem::new(
+                        "p1".to_string(),
+                        "/srv/開発開発開発/mdcat".to_string(),
+                        Vec::new(),
+                    ),
+                ),
+                (
+                    "2",
+                    AppLaunchItem::new(
+                        "p2".to_string(),
+                        "/srv/devdev/mdcat".to_string(),
+                        Vec::new(),
+                    ),
+                ),
+            ];
+            let score = |id: &str| {
+                let (_, item) = items.iter().find(|(i, _)| *i == id).unwrap();
+                item.match_score_relative_to(&Query::parse(&["mdcat"]), &["/home/foo"])
+            };
+            assert_eq!(score("1"), score("2"));
+        }
+
+        #[test]
+        fn matches_emoji_paths() {
+            let items = vec![(
+                "1",
+                AppLaunchItem::new(
+                    "crab".to_string(),
+                    "/home/foo/🦀/crab".to_string(),
+                    Vec::new(),
+                ),
+            )];
+            assert_eq!(do_match(&items, &["🦀"]), ["1"]);
+        }
+
+        #[test]
+        fn path_terms_match_items_at_roots() {
+            let item =
+                AppLaunchItem::new("dotfiles".to_string(), "/home/foo".to_string(), Vec::new());
+            assert!(
+                0.0 < item.match_score_relative_to(&Query::parse(&["path:foo"]), &["/home/foo"])
+            );
+        }
+
+        #[test]
+        fn activations_rank_higher_within_same_kind_of_match() {
+            let now = 100_000;
+            let history = history(vec![
+                activation(now, "1", &["mdcat"]),
+                activation(now, "3", &["mdcat"]),
+            ]);
+            let items = vec![
+                (
+                    "1",
+                    AppLaunchItem::new(
+                        "mdcat-old".to_string(),
+                        "/home/foo/dev/mdcat-old".to_string(),
+                        Vec::new(),
+                    ),
+                ),
+                (
+                    "2",
+                    AppLaunchItem::new(
+                        "mdcat".to_string(),
+                        "/home/foo/dev/mdcat".to_string(),
+                        Vec::new(),
+                    ),
+                ),
+                (
+                    "3",
+                    AppLaunchItem::new(
+                        "docs".to_string(),
+                        "/home/foo/mdcat/docs".to_string(),
+                        Vec::new(),
+                    ),
+                ),
+            ];
+            let matches = find_matching_items_with_boost(
+                items.iter().map(|(s, p)| (*s, p.relative_to(ROOTS))),
+                &Query::parse(&["md"]),
+                0.0,
+                |id| history.boost_at(id, &["md"], now),
+            );
+            assert_eq!(matches, ["1", "2", "3"]);
+        }
+
+        #[test]
+        fn discard_matches_below_min_score() {
+            let items = vec![
+                (
+                    "1",
+                    AppLaunchItem::new(
+                        "mdcat".to_string(),
+                        "/home/foo/dev/mdcat".to_string(),
+                        Vec::new(),
+                    ),
+                ),
+                (
+                    "2",
+                    AppLaunchItem::new(
+                        "docs".to_string(),
+                        "/home/foo/mdcat/docs".to_string(),
+                        Vec::new(),
+                    ),
+                ),
+            ];
+            let matches = find_matching_items_with_boost(
+                items.iter().map(|(s, p)| (*s, p.relative_to(ROOTS))),
+                &Query::parse(&["mdcat"]),
+                2.0,
+                |_| 0.0,
+            );
+            assert_eq!(matches, ["1"]);
+        }
     }
 }
@@ -6,52 +6,284 @@
 
 //! Items to be launch by an app.
 
-use crate::matching::ScoreMatchable;
+use std::path::Path;
+
+use crate::matching::{
+    component_score, normalize, position_score, Query, QueryField, QueryTerm, ScoreMatchable,
+};
+use crate::paths::{home_dir, strip_roots};
 use tracing::field;
 use tracing::{instrument, trace};
 
 /// A recent item from the file system.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct AppLaunchItem {
     /// The human readable name
-    pub name: String,
+    name: String,
 
     /// The URI to launch when activating this item.
-    pub uri: String,
+    uri: String,
+
+    /// Additional keywords describing this item.
+    keywords: Vec<String>,
+
+    /// The normalized fields of this item, to match search terms against.
+    fields: MatchFields,
 }
 
-impl ScoreMatchable for AppLaunchItem {
-    /// Compute the score of matching self against `terms`.
+impl AppLaunchItem {
+    /// Create a new item with the given `name`, `uri` and `keywords`.
+    ///
+    /// Keywords match with a lower weight than name and URI.
+    pub fn new(name: String, uri: String, keywords: Vec<String>) -> Self {
+        let fields = MatchFields::new(&name, &uri, &keywords);
+        Self {
+            name,
+            uri,
+            keywords,
+            fields,
+        }
+    }
+
+    /// The human readable name of this item.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The URI to launch when activating this item.
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Additional keywords describing this item.
+    pub fn keywords(&self) -> &[String] {
+        &self.keywords
+    }
+}
+
+/// The score of a matching name term.
+const NAME_SCORE: f64 = 10.0;
+
+/// The score of a matching keyword term.
+const KEYWORD_SCORE: f64 = 0.5;
+
+/// The score of items which only need to pass qualifiers and negations.
+///
+/// Used for queries without any positive terms for items, e.g. `ide:rider -archive`.
+const FILTER_ONLY_SCORE: f64 = 0.1;
+
+/// The weight of the component score in the score of a path term.
+///
+/// The position score makes up the rest, and mostly breaks ties between equal components.
+const COMPONENT_WEIGHT: f64 = 0.8;
+
+/// Normalized fields of an item for matching, see [`normalize`].
+#[derive(Debug, Clone, PartialEq)]
+struct MatchFields {
+    name: String,
+    directory_name: String,
+    uri: String,
+    keywords: Vec<String>,
+}
+
+impl MatchFields {
+    fn new(name: &str, uri: &str, keywords: &[String]) -> Self {
+        let directory_name = Path::new(uri)
+            .file_name()
+            .map(|s| normalize(&s.to_string_lossy()))
+            .unwrap_or_default();
+        Self {
+            name: normalize(name),
+            directory_name,
+            uri: normalize(uri),
+            keywords: keywords.iter().map(|k| normalize(k)).collect(),
+        }
+    }
+
+    /// Get a view of these fields with the URI relative to the longest of the normalized `roots`.
+    fn relative_to<S: AsRef<str>>(&self, roots: &[S]) -> RelativeFields<'_> {
+        RelativeFields {
+            fields: self,
+            uri: strip_roots(&self.uri, roots),
+        }
+    }
+}
+
+/// Normalized fields of an item, with the URI relative to a root.
+struct RelativeFields<'a> {
+    fields: &'a MatchFields,
+    uri: &'a str,
+}
+
+impl RelativeFields<'_> {
+    fn matches_name(&self, term: &str) -> bool {
+        self.fields.name.contains(term) || self.fields.directory_name.contains(term)
+    }
+
+    /// Score `term` by the path component of the URI it matches, and by its position in the URI.
+    ///
+    /// See [`component_score`] and [`position_score`].
+    fn path_score(&self, term: &str) -> Option<f64> {
+        let component = component_score(self.uri, term)?;
+        let position = position_score(self.uri, term)?;
+        Some(COMPONENT_WEIGHT * component + (1.0 - COMPONENT_WEIGHT) * position)
+    }
+
+    fn matches_any(&self, term: &str) -> bool {
+        self.matches_name(term)
+            || self.uri.contains(term)
+            || self
+                .fields
+                .keywords
+                .iter()
+                .any(|keyword| keyword.contains(term))
+    }
+
+    fn matches(&self, term: &QueryTerm) -> bool {
+        match term.field {
+            None => self.matches_any(&term.text),
+            Some(QueryField::Name) => self.matches_name(&term.text),
+            Some(QueryField::Path) => self.uri.contains(&term.text),
+            // The provider checks the app, not the item
+            Some(QueryField::App) => false,
+        }
+    }
+}
+
+impl AppLaunchItem {
+    /// Compute the score of matching self against `query`, with paths relative to `roots`.
     ///
     /// If all terms match the name each term contributes a score of 10; this makes sure
-    /// that precise matches in the name boost the score somewhat to the top.
-    ///
-    /// If all terms match the target each term contributes 1 to score, scaled by the relative position
-    /// of the right-most match, assuming that paths typically go from least to most specific segment,
-    /// to the farther to the right a term matches the more specific it was.
-    #[instrument(skip(terms), fields(terms = field::debug(terms.iter().map(|s| s.as_ref()).collect::<Vec<&str>>())))]
-    fn match_score<S: AsRef<str>>(&self, terms: &[S]) -> f64 {
-        let name = self.name.to_lowercase();
-        let uri = self.uri.to_lowercase();
-        let name_score = terms.iter().try_fold(0.0, |score, term| {
-            name.contains(&term.as_ref().to_lowercase())
-                .then(|| score + 10.0)
+    /// that precise matches in the name boost the score somewhat to the top.  A term matches
+    /// the name if it matches either the human readable name or the last segment of the URI,
+    /// i.e. the directory name of a project, in case both differ.
+    ///
+    /// If all terms match the target each term contributes up to 1 to score, depending mostly on the
+    /// path component it matches: whole components score more than prefixes of components, and the
+    /// final component scores most, assuming that paths typically go from least to most specific
+    /// segment.  The relative position of the right-most match breaks ties.  The target is relative to
+    /// the longest of the given `roots`, so that the parts of paths which all items share, e.g. the home
+    /// directory, don't match.  `roots` must be normalized with [`normalize`], like the target.
+    ///
+    /// If all terms match either name, target or any keyword, each term contributes another 0.5 to the
+    /// score; this finds items by their keywords, e.g. build system or organisation, but ranks them
+    /// below items which match by name or target.
+    ///
+    /// `query` can also contain qualified terms: `name:` terms must match the name
+    /// and `path:` terms the target, and contribute to the score like plain terms; `ide:` terms are
+    /// ignored, because they refer to the app and not the item.  Any match for a negated term like
+    /// `-archive` rejects the item.
+    #[instrument(skip(query, roots), fields(query = field::debug(query)))]
+    pub fn match_score_relative_to<R: AsRef<str>>(&self, query: &Query, roots: &[R]) -> f64 {
+        let fields = self.fields.relative_to(roots);
+
+        if let Some(term) = query.negated_terms().find(|term| fields.matches(term)) {
+            trace!("Item {:?} excluded by negated term {:?}", self, term);
+            return 0.0;
+        }
+
+        let qualified = query
+            .field_terms(QueryField::Name)
+            .try_fold(0.0, |score, term| {
+                fields
+                    .matches_name(term)
+                    .then(|| score + NAME_SCORE)
+                    .ok_or(())
+            })
+            .and_then(|score| {
+                query
+                    .field_terms(QueryField::Path)
+                    .try_fold(score, |score, term| {
+                        fields.path_score(term).map(|s| score + s).ok_or(())
+                    })
+            });
+        let qualified = match qualified {
+            Ok(score) => score,
+            Err(_) => {
+                trace!(
+                    "Item {:?} doesn't match qualified terms of {:?}",
+                    self,
+                    query
+                );
+                return 0.0;
+            }
+        };
+
+        let plain: Vec<&str> = query.plain_terms().collect();
+        let name_score = plain.iter().try_fold(0.0, |score, term| {
+            fields
+                .matches_name(term)
+                .then(|| score + NAME_SCORE)
                 .ok_or(())
         });
-        let target = terms.iter().try_fold(0.0, |score, term| {
-            uri.rfind(&term.as_ref().to_lowercase())
+        let target = plain.iter().try_fold(0.0, |score, term| {
+            fields.path_score(term).map(|s| score + s).ok_or(())
+        });
+        let keywords_score = plain.iter().try_fold(0.0, |score, term| {
+            fields
+                .matches_any(term)
+                .then(|| score + KEYWORD_SCORE)
                 .ok_or(())
-                .map(|index| score + 1.0 * (index as f64 / uri.len() as f64))
         });
-        let score = name_score.unwrap_or_default() + target.unwrap_or_default();
+        let plain_score = name_score.unwrap_or_default()
+            + target.unwrap_or_default()
+            + keywords_score.unwrap_or_default();
+
+        let score = if !plain.is_empty() && plain_score <= 0.0 {
+            // Plain terms must match as well, if any
+            0.0
+        } else if plain.is_empty() && qualified <= 0.0 {
+            // Only negated or app terms, so the item only needs to pass these
+            if query.terms.is_empty() {
+                0.0
+            } else {
+                FILTER_ONLY_SCORE
+            }
+        } else {
+            plain_score + qualified
+        };
         trace!(
-            "Item {:?} matches terms {:?} with score {} (name={:?}, target={:?})",
+            "Item {:?} matches query {:?} with score {} (name={:?}, target={:?}, keywords={:?}, qualified={})",
             self,
-            terms.iter().map(|s| s.as_ref()).collect::<Vec<&str>>(),
+            query,
             score,
             name_score,
-            target
+            target,
+            keywords_score,
+            qualified
         );
         score
     }
+
+    /// Get a view of this item which matches paths relative to the given normalized `roots`.
+    ///
+    /// See [`AppLaunchItem::match_score_relative_to`].
+    pub fn relative_to<'a, R: AsRef<str>>(&'a self, roots: &'a [R]) -> RelativeItem<'a, R> {
+        RelativeItem { item: self, roots }
+    }
+}
+
+/// An item which matches paths relative to a set of root directories.
+#[derive(Debug)]
+pub struct RelativeItem<'a, R: AsRef<str>> {
+    item: &'a AppLaunchItem,
+    roots: &'a [R],
+}
+
+impl<R: AsRef<str>> ScoreMatchable for RelativeItem<'_, R> {
+    fn match_score(&self, query: &Query) -> f64 {
+        self.item.match_score_relative_to(query, self.roots)
+    }
+}
+
+impl ScoreMatchable for AppLaunchItem {
+    /// Compute the score of matching self against `query`, with paths relative to the home directory.
+    ///
+    /// This normalizes the home directory for every item; to match many items use
+    /// [`AppLaunchItem::relative_to`] with normalized roots instead.
+    ///
+    /// See [`AppLaunchItem::match_score_relative_to`].
+    fn match_score(&self, query: &Query) -> f64 {
+        self.match_score_relative_to(query, &[normalize(&home_dir())])
+    }
 }
@@ -0,0 +1,113 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Stable IDs for recent items of apps.
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::app::AppId;
+
+/// The prefix of every item ID.
+const PREFIX: &str = "app-item";
+
+/// The version of the current ID scheme.
+///
+/// Bump this whenever the format of IDs changes in an incompatible way.
+const VERSION: &str = "v1";
+
+/// The ID of a recent item of an app.
+///
+/// Identifies an item by the app it belongs to and by the URI the app launches for the item.
+/// The string representation has the form `app-item:v1:<app ID>:<URI>`, where the app ID and
+/// the URI are percent-encoded, so the ID contains no characters which could confuse Gnome
+/// Shell or the parser, and remains the same across restarts of the service.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AppItemId {
+    /// The app this item belongs to.
+    app: AppId,
+    /// The URI of the item.
+    uri: String,
+}
+
+impl AppItemId {
+    /// Create a new ID for the item at `uri` of `app`.
+    pub fn new(app: AppId, uri: String) -> Self {
+        Self { app, uri }
+    }
+
+    /// The app of the item.
+    pub fn app(&self) -> &AppId {
+        &self.app
+    }
+
+    /// The URI of the item.
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+}
+
+impl Display for AppItemId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}:{}",
+            PREFIX,
+            VERSION,
+            glib::uri_escape_string(&self.app.to_string(), None, false),
+            glib::uri_escape_string(&self.uri, None, false)
+        )
+    }
+}
+
+/// An error while parsing an item ID.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AppItemIdParseError {
+    /// The ID did not start with the expected prefix.
+    InvalidPrefix,
+    /// The ID used an unsupported version of the ID scheme.
+    UnsupportedVersion,
+    /// The app ID or URI part were missing or not properly encoded.
+    InvalidComponent,
+}
+
+impl Display for AppItemIdParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            AppItemIdParseError::InvalidPrefix => "invalid prefix",
+            AppItemIdParseError::UnsupportedVersion => "unsupported version",
+            AppItemIdParseError::InvalidComponent => "invalid app ID or URI",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl FromStr for AppItemId {
+    type Err = AppItemIdParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        if parts.next() != Some(PREFIX) {
+            return Err(AppItemIdParseError::InvalidPrefix);
+        }
+        if parts.next() != Some(VERSION) {
+            return Err(AppItemIdParseError::UnsupportedVersion);
+        }
+        let mut component = || {
+            parts
+                .next()
+                .filter(|s| !s.is_empty())
+                .and_then(|s| glib::uri_unescape_string(s, None))
+                .ok_or(AppItemIdParseError::InvalidComponent)
+        };
+        let app = component()?;
+        let uri = component()?;
+        if parts.next().is_some() {
+            return Err(AppItemIdParseError::InvalidComponent);
+        }
+        Ok(Self::new(app.as_str().into(), uri.to_string()))
+    }
+}
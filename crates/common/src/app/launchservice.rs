@@ -6,9 +6,13 @@
 
 //! A service to launch apps on a given Glib main context.
 
+use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::ffi::{OsStr, OsString};
 use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use gio::prelude::*;
 use glib::SourceId;
@@ -19,24 +23,117 @@ use tracing::{debug, error, info, span, trace, warn};
 use tracing::{instrument, Level, Span};
 use tracing_futures::Instrument;
 use zbus::zvariant::OwnedObjectPath;
+use zbus::CacheProperties;
 
+use crate::app::scopes::{track_scope, LaunchedScope, LaunchedScopes};
 use crate::glib::VariantDict;
 use crate::systemd::*;
 
+/// What to do with processes of launched apps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScopeMode {
+    /// Leave processes of launched apps in the cgroup of the service.
+    Disabled,
+    /// Move processes of launched apps to new scopes.
+    ///
+    /// Leave a process in the cgroup of the service if moving it fails.
+    Fallback,
+    /// Like [`ScopeMode::Fallback`], but retry with a unique name if the scope already exists.
+    Retry,
+}
+
+impl Default for ScopeMode {
+    fn default() -> Self {
+        ScopeMode::Fallback
+    }
+}
+
+/// How often to attempt to start a scope in [`ScopeMode::Retry`].
+const MAX_SCOPE_ATTEMPTS: u32 = 3;
+
 /// Settings for systemd scopes created by a search provider for launched apps.
 #[derive(Debug, Clone)]
 pub struct SystemdScopeSettings {
+    /// Whether and how to move launched apps to scopes.
+    pub mode: ScopeMode,
     /// The prefix for scope names.
     pub prefix: String,
     /// A string which identifies the app which started the scope.
     pub started_by: String,
     /// Optional documentation URLs for the scope.
     pub documentation: Vec<String>,
+    /// Resource controls for scopes of all apps.
+    pub resources: ScopeResources,
+    /// Resource controls for scopes of individual apps by desktop ID, instead of `resources`.
+    pub app_resources: HashMap<String, ScopeResources>,
 }
 
-#[instrument(skip(connection))]
+impl SystemdScopeSettings {
+    /// Get the resource controls for the scope of the app with the given desktop `id`.
+    pub fn resources_for(&self, id: &str) -> &ScopeResources {
+        self.app_resources.get(id).unwrap_or(&self.resources)
+    }
+}
+
+/// Launches and scope moves in progress.
+///
+/// Cloning returns another handle to the same launches.
+#[derive(Debug, Clone, Default)]
+pub struct PendingLaunches {
+    count: Arc<AtomicUsize>,
+}
+
+/// A launch or scope move in progress, until dropped.
+#[derive(Debug)]
+struct PendingLaunch {
+    count: Arc<AtomicUsize>,
+}
+
+impl Drop for PendingLaunch {
+    fn drop(&mut self) {
+        self.count.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl PendingLaunches {
+    /// Start a new launch, which remains pending until the returned value is dropped.
+    fn start(&self) -> PendingLaunch {
+        self.count.fetch_add(1, Ordering::SeqCst);
+        PendingLaunch {
+            count: self.count.clone(),
+        }
+    }
+
+    /// The number of launches and scope moves in progress.
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    /// Wait until all pending launches completed, but not longer than `timeout`.
+    ///
+    /// Return `true` if all launches completed, and `false` if some were still pending after
+    /// `timeout`.  Uses Glib timeouts, so the returned future must run on a Glib main context.
+    pub async fn wait(&self, timeout: Duration) -> bool {
+        let start = Instant::now();
+        loop {
+            let count = self.count();
+            if count == 0 {
+                return true;
+            }
+            let elapsed = start.elapsed();
+            if timeout <= elapsed {
+                warn!("{} launch(es) still pending after {:?}", count, elapsed);
+                return false;
+            }
+            trace!("Waiting for {} pending launch(es)", count);
+            glib::timeout_future((timeout - elapsed).min(Duration::from_millis(50))).await;
+        }
+    }
+}
+
+#[instrument(skip(systemd))]
 async fn move_launched_process_to_scope(
-    connection: &zbus::Connection,
+    systemd: &Systemd1ManagerProxy<'_>,
     id: &str,
     description: &str,
     scope_settings: &SystemdScopeSettings,
@@ -53,14 +150,63 @@ async fn move_launched_process_to_scope(
             .iter()
             .map(|v| v.as_str())
             .collect(),
+        resources: scope_settings.resources_for(id),
     };
-    trace!("Connect to Systemd1 Manager object");
-    let systemd = Systemd1ManagerProxy::new(connection).await?;
     debug!(
         "Starting scope for process {} of app {} with properties: {:?}",
         pid, id, &properties
     );
-    start_app_scope(&systemd, properties, pid).await
+    let attempts = if scope_settings.mode == ScopeMode::Retry {
+        MAX_SCOPE_ATTEMPTS
+    } else {
+        1
+    };
+    let mut attempt = 0;
+    loop {
+        match start_app_scope(systemd, &properties, pid, attempt).await {
+            Err(error) if is_unit_exists_error(&error) && attempt + 1 < attempts => {
+                debug!(
+                    "Scope for process {} of app {} already exists, retrying with unique name",
+                    pid, id
+                );
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Move the launched process `pid` of the app `id` to a new scope, as per `scope_settings`.
+///
+/// Return the name and the object path of the new scope, or `None` if scopes are disabled or if
+/// there's no systemd manager on `connection`.
+#[instrument(skip(connection))]
+async fn create_scope_for_launched_process(
+    connection: &zbus::Connection,
+    id: &str,
+    description: &str,
+    scope_settings: &SystemdScopeSettings,
+    pid: pid_t,
+) -> Result<Option<(String, OwnedObjectPath)>, zbus::Error> {
+    if scope_settings.mode == ScopeMode::Disabled {
+        debug!("Scopes disabled, leaving process {} of app {}", pid, id);
+        return Ok(None);
+    }
+    if !systemd_manager_available(connection).await {
+        warn!(
+            "No systemd manager available, leaving process {} of app {} in the cgroup of this service",
+            pid, id
+        );
+        return Ok(None);
+    }
+    trace!("Connect to Systemd1 Manager object");
+    let systemd = Systemd1ManagerProxy::builder(connection)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    move_launched_process_to_scope(&systemd, id, description, scope_settings, pid)
+        .await
+        .map(Some)
 }
 
 fn get_pid(value: &glib::Variant) -> Option<pid_t> {
@@ -121,6 +267,12 @@ pub struct App {
 }
 
 impl App {
+    /// Create an app with the given `id` and `icon`, without a desktop file.
+    #[cfg(test)]
+    pub(crate) fn new(id: AppId, icon: String) -> Self {
+        Self { id, icon }
+    }
+
     /// The ID of this app.
     pub fn id(&self) -> &AppId {
         &self.id
@@ -143,6 +295,90 @@ impl From<gio::DesktopAppInfo> for App {
     }
 }
 
+/// How to launch apps, in addition to what their desktop files say.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LaunchOptions {
+    /// Environment variables to set for launched apps, as pairs of name and value.
+    pub env: Vec<(String, String)>,
+    /// Names of environment variables to remove from the environment of launched apps.
+    pub unset_env: Vec<String>,
+    /// Extra arguments to pass to launched apps, before the URI.
+    ///
+    /// Apps which are activated over DBus instead of executed ignore these arguments.
+    pub args: Vec<String>,
+}
+
+impl LaunchOptions {
+    /// Apply the environment of these options to `context`.
+    fn apply_env(&self, context: &gio::AppLaunchContext) {
+        for name in &self.unset_env {
+            context.unsetenv(name);
+        }
+        for (name, value) in &self.env {
+            context.setenv(name, value);
+        }
+    }
+}
+
+/// Whether `arg` is a field code of the `Exec` key of a desktop file, e.g. `%u`.
+fn is_field_code(arg: &str) -> bool {
+    arg.len() == 2 && arg.starts_with('%')
+}
+
+/// Insert `args` after the program in the command line `exec` of a desktop file.
+///
+/// Quote all arguments except for field codes, which Gio expands on launch.  Gio expands field
+/// codes even inside quotes, so escape `%` in `args` as `%%`.
+fn insert_args(exec: &str, args: &[String]) -> Result<String, glib::Error> {
+    let argv = glib::shell_parse_argv(exec)?;
+    let mut argv = argv.iter().map(|arg| arg.to_string_lossy().into_owned());
+    let program = argv.next().ok_or_else(|| {
+        glib::Error::new(
+            glib::FileError::Inval,
+            &format!("No program in command line {}", exec),
+        )
+    })?;
+    Ok(std::iter::once(program)
+        .chain(args.iter().map(|arg| arg.replace('%', "%%")))
+        .chain(argv)
+        .map(|arg| {
+            if is_field_code(&arg) {
+                arg
+            } else {
+                glib::shell_quote(&arg).to_string_lossy().into_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+/// Get a copy of `app` which passes `args` to the app, before the URI.
+///
+/// Load the desktop file of `app` and insert `args` into its command line.
+fn with_extra_args(
+    app: &gio::DesktopAppInfo,
+    args: &[String],
+) -> Result<gio::DesktopAppInfo, glib::Error> {
+    let filename = app.filename().ok_or_else(|| {
+        glib::Error::new(
+            glib::FileError::Noent,
+            &format!("App {:?} has no desktop file", app.id()),
+        )
+    })?;
+    let keyfile = glib::KeyFile::new();
+    keyfile.load_from_file(&filename, glib::KeyFileFlags::KEEP_TRANSLATIONS)?;
+    let group = *glib::KEY_FILE_DESKTOP_GROUP;
+    let key = *glib::KEY_FILE_DESKTOP_KEY_EXEC;
+    let exec = keyfile.string(group, key)?;
+    keyfile.set_string(group, key, &insert_args(&exec, args)?);
+    gio::DesktopAppInfo::from_keyfile(&keyfile).ok_or_else(|| {
+        glib::Error::new(
+            glib::FileError::Inval,
+            &format!("Invalid desktop file {}", filename.display()),
+        )
+    })
+}
+
 /// Request to launch an app, with an URI to launch with.
 #[derive(Debug)]
 struct AppLaunchRequest {
@@ -150,6 +386,12 @@ struct AppLaunchRequest {
     app: AppId,
     /// The URI to pass to the app on launch.
     uri: Option<String>,
+    /// How to launch the app.
+    options: Arc<LaunchOptions>,
+    /// A command to run on behalf of the app instead of launching the app itself.
+    ///
+    /// The URI is not appended to this command; it only records which URI the process opens.
+    command: Option<Vec<OsString>>,
     /// A one-shot channel to send the result of launching the app to.
     response: futures_channel::oneshot::Sender<Result<(), glib::Error>>,
     /// The span in which to trace launching.
@@ -160,8 +402,14 @@ async fn launch_app(
     context: &gio::AppLaunchContext,
     app_id: &AppId,
     uri: Option<&str>,
+    args: &[String],
 ) -> Result<(), glib::Error> {
     let app = gio::DesktopAppInfo::try_from(app_id)?;
+    let app = if args.is_empty() {
+        app
+    } else {
+        with_extra_args(&app, args)?
+    };
     debug!("Launching App {:?} with uri {:?}", app, &uri);
     match uri {
         None => app.launch_uris_async_future(&[], Some(context)),
@@ -170,12 +418,86 @@ async fn launch_app(
     .await
 }
 
-#[instrument(skip(main_context, connection))]
-fn handle_launched(
-    main_context: glib::MainContext,
+/// Everything needed to move launched processes to scopes, and to track these scopes.
+#[derive(Debug, Clone)]
+struct ScopeContext {
+    /// The DBus connection to talk to systemd.
     connection: zbus::Connection,
-    scope_settings: Arc<SystemdScopeSettings>,
+    /// How to set up scopes.
+    settings: Arc<SystemdScopeSettings>,
+    /// Where to record created scopes.
+    scopes: LaunchedScopes,
+}
+
+/// Move the launched process `pid` of the app `id` to a new scope, and track that scope.
+///
+/// `description` describes the app for the description of the scope.  `uri` is the URI the
+/// process opens, if any.  The process counts as pending until it moved to its scope.
+///
+/// Return a receiver which completes once the process moved to its scope, or failed to.
+fn move_to_scope(
+    main_context: &glib::MainContext,
+    scope_context: &ScopeContext,
+    pending: &PendingLaunches,
+    id: &AppId,
+    description: Option<&str>,
+    uri: Option<&str>,
+    pid: pid_t,
+) -> futures_channel::oneshot::Receiver<()> {
+    let (moved, receiver) = futures_channel::oneshot::channel();
+    let id = id.to_string();
+    let started_by = &scope_context.settings.started_by;
+    let description = description.map_or_else(
+        || format!("app started by {}", started_by),
+        |value| format!("{} started by {}", value, started_by),
+    );
+    let uri = uri.map(ToString::to_string);
+    let scope_context = scope_context.clone();
+    let pending = pending.start();
+    main_context.spawn(async move {
+        let result = create_scope_for_launched_process(
+            &scope_context.connection,
+            &id,
+            &description,
+            &scope_context.settings,
+            pid,
+        )
+            .await;
+        // The launch completed once the process is in its scope, even though we keep
+        // tracking the scope.
+        drop(pending);
+        // We don't care if nobody waits for the process to move
+        let _ = moved.send(());
+        match result {
+            Err(err) => {
+                error!("Failed to move running process {} of app {} into new systemd scope, leaving it in the cgroup of this service: {}", pid, id, err);
+            },
+            Ok(None) => {},
+            Ok(Some((name, path))) => {
+                info!("Moved running process {} of app {} into new systemd scope {} at {}", pid, id, &name, path.as_str());
+                scope_context.scopes.insert(LaunchedScope {
+                    name: name.clone(),
+                    path: path.clone(),
+                    app: id.into(),
+                    uri,
+                    pid,
+                    active_state: "active".to_string(),
+                });
+                track_scope(&scope_context.connection, &scope_context.scopes, &name, path).await;
+            },
+        };
+    }.in_current_span());
+    receiver
+}
+
+#[instrument(skip(main_context, scope_context, pending))]
+fn handle_launched(
+    main_context: &glib::MainContext,
+    scope_context: &ScopeContext,
+    pending: &PendingLaunches,
+    id: &AppId,
     app: &gio::AppInfo,
+    uri: Option<&str>,
     platform_data: &glib::Variant,
 ) {
     match get_pid(platform_data) {
@@ -186,43 +508,162 @@ fn handle_launched(
             );
         }
         Some(pid) => {
-            info!("App {} launched with PID {}", app.id().unwrap(), pid);
-            let id = app.id().unwrap().to_string();
-            let description = app.description().map_or_else(
-                || format!("app started by {}", scope_settings.started_by),
-                |value| format!("{} started by {}", value, scope_settings.started_by),
+            info!("App {} launched with PID {}", id, pid);
+            // Apps with extra arguments come from a key file and have no ID, so take the ID of
+            // the request.  The launch itself replies once the app launched.
+            let _ = move_to_scope(
+                main_context,
+                scope_context,
+                pending,
+                id,
+                app.description().as_deref(),
+                uri,
+                pid,
             );
-            main_context.spawn(async move {
-                let result = move_launched_process_to_scope(
-                    &connection,
-                    &id,
-                    &description,
-                    &scope_settings,
-                    pid,
-                )
-                    .await;
-                match result {
-                    Err(err) => {
-                        error!("Failed to move running process {} of app {} into new systemd scope: {}", pid, id, err);
-                    },
-                    Ok((name, path)) => {
-                        info!("Moved running process {} of app {} into new systemd scope {} at {}",pid, id, &name, path.into_inner());
-                    },
-                };
-            }.in_current_span())
         }
     }
 }
 
-fn handle_launch(
+/// Spawn `argv` with the environment of `options`.
+fn spawn_command(
+    argv: &[OsString],
+    options: &LaunchOptions,
+) -> Result<gio::Subprocess, glib::Error> {
+    let launcher = gio::SubprocessLauncher::new(gio::SubprocessFlags::NONE);
+    for name in &options.unset_env {
+        launcher.unsetenv(name);
+    }
+    for (name, value) in &options.env {
+        launcher.setenv(name, value, true);
+    }
+    launcher.spawn(
+        &argv
+            .iter()
+            .map(OsString::as_os_str)
+            .collect::<Vec<&OsStr>>(),
+    )
+}
+
+/// Run `argv` for the app of `request`, and reply once the process moved to a scope.
+///
+/// Log the exit status of the process, but don't wait for it; the process may well be a new
+/// instance of the app.
+fn handle_command(
     main_context: glib::MainContext,
-    launch_context: gio::AppLaunchContext,
+    scope_context: &ScopeContext,
+    pending: &PendingLaunches,
     request: AppLaunchRequest,
+    argv: Vec<OsString>,
+) {
+    let span = span!(parent: request.span.clone(), Level::INFO, "handle_command", request.app = field::debug(&request.app), argv = field::debug(&argv));
+    let _guard = span.enter();
+    let process = match spawn_command(&argv, &request.options) {
+        Ok(process) => process,
+        Err(error) => {
+            warn!(
+                "Failed to run {:?} for app {}: {}",
+                argv, request.app, error
+            );
+            // We don't care if the receiver already dropped their side of the channel
+            let _ = request.response.send(Err(error));
+            return;
+        }
+    };
+    let moved = match process
+        .identifier()
+        .and_then(|identifier| identifier.parse::<pid_t>().ok())
+    {
+        None => {
+            warn!("Failed to get PID of {:?} for app {}", argv, request.app);
+            None
+        }
+        Some(pid) => {
+            info!(
+                "Started {:?} for app {} with PID {}",
+                argv, request.app, pid
+            );
+            let description = gio::DesktopAppInfo::try_from(&request.app)
+                .ok()
+                .and_then(|app| app.description());
+            Some(move_to_scope(
+                &main_context,
+                scope_context,
+                pending,
+                &request.app,
+                description.as_deref(),
+                request.uri.as_deref(),
+                pid,
+            ))
+        }
+    };
+    let app = request.app;
+    let response = request.response;
+    main_context.spawn_local(
+        async move {
+            if let Some(moved) = moved {
+                // Moving only fails if the main context went away; we logged other failures
+                let _ = moved.await;
+            }
+            // We don't care if the receiver already dropped their side of the channel
+            let _ = response.send(Ok(()));
+            match process.wait_check_async_future().await {
+                Ok(()) => debug!("{:?} for app {} exited", argv, app),
+                Err(error) => warn!("{:?} for app {} failed: {}", argv, app, error),
+            }
+        }
+        .in_current_span(),
+    );
+}
+
+fn handle_launch(
+    main_context: glib::MainContext,
+    scope_context: &ScopeContext,
+    pending: &PendingLaunches,
+    mut request: AppLaunchRequest,
 ) {
+    if let Some(argv) = request.command.take() {
+        handle_command(main_context, scope_context, pending, request, argv);
+        return;
+    }
+    // Use a separate launch context for every request, to know the URI of launched processes.
+    let launch_context = gio::AppLaunchContext::new();
+    request.options.apply_env(&launch_context);
+    // Move every launched process to a scope, even if the app just hands the URI to a running
+    // instance and exits: the running instance might be shutting down, so that the process
+    // starts a new instance instead.
+    let id = request.app.clone();
+    let uri = request.uri.clone();
+    let scope_context = scope_context.clone();
+    let pending_scopes = pending.clone();
+    launch_context.connect_launched(
+        glib::clone!(@strong main_context => move |_, app, platform_data| {
+            trace!(
+                "App {} launched with platform_data: {:?}",
+                id,
+                platform_data
+            );
+            handle_launched(
+                &main_context,
+                &scope_context,
+                &pending_scopes,
+                &id,
+                app,
+                uri.as_deref(),
+                platform_data,
+            )
+        }),
+    );
+    let pending = pending.start();
     main_context.spawn_local(async move {
+        let _pending = pending;
         // We don't care if the receiver already dropped their side of the channel
         let _ = request.response.send(
-            launch_app(&launch_context, &request.app, request.uri.as_deref())
+            launch_app(
+                &launch_context,
+                &request.app,
+                request.uri.as_deref(),
+                &request.options.args,
+            )
                 .instrument(
                     span!(parent: request.span.clone(), Level::INFO, "handle_launch", request.app = field::debug(&request.app), request.uri = field::debug(&request.uri)),
                 )
@@ -236,17 +677,25 @@ fn handle_launch(
 /// A launch service receives requests to launch apps from launch service clients.
 ///
 /// For each request it then starts an instance of the app with the given URI if any,
-/// and moves the new process into a dedicated systemd scope to isolate it.
+/// and moves the new process into a dedicated systemd scope to isolate it.  It then tracks
+/// the state of the scope until the scope is gone.
 #[derive(Debug)]
 pub struct AppLaunchService {
     recv: glib::Receiver<AppLaunchRequest>,
     send: glib::Sender<AppLaunchRequest>,
+    pending: PendingLaunches,
+    scopes: LaunchedScopes,
 }
 
 impl Default for AppLaunchService {
     fn default() -> Self {
         let (send, recv) = glib::MainContext::channel(glib::Priority::default());
-        AppLaunchService { send, recv }
+        AppLaunchService {
+            send,
+            recv,
+            pending: PendingLaunches::default(),
+            scopes: LaunchedScopes::default(),
+        }
     }
 }
 
@@ -275,40 +724,42 @@ impl AppLaunchService {
         connection: zbus::Connection,
         scope_settings: SystemdScopeSettings,
     ) -> (AppLaunchClient, SourceId) {
-        let scope_settings_arc = Arc::new(scope_settings);
-        let launch_context = gio::AppLaunchContext::new();
-        launch_context.connect_launched(
-            glib::clone!(@strong main_context => move |_, app, platform_data| {
-                trace!(
-                    "App {} launched with platform_data: {:?}",
-                    app.id().unwrap(),
-                    platform_data
-                );
-                handle_launched(
-                    main_context.clone(),
-                    connection.clone(),
-                    scope_settings_arc.clone(),
-                    app,
-                    platform_data,
-                )
-            }),
-        );
+        let scope_context = ScopeContext {
+            connection,
+            settings: Arc::new(scope_settings),
+            scopes: self.scopes.clone(),
+        };
+        let pending = self.pending.clone();
 
         let client = self.client();
         let source = self.recv.attach(
             Some(main_context),
             glib::clone!(@strong main_context => move |request: AppLaunchRequest| {
-                handle_launch(main_context.clone(), launch_context.clone(), request);
+                handle_launch(main_context.clone(), &scope_context, &pending, request);
                 glib::Continue(true)
             }),
         );
         (client, source)
     }
 
+    /// Get the launches in progress in this service.
+    ///
+    /// Every request counts as pending until the app launched, and every launched process until
+    /// it moved to its systemd scope.
+    pub fn pending(&self) -> PendingLaunches {
+        self.pending.clone()
+    }
+
+    /// Get the scopes of apps launched by this service.
+    pub fn scopes(&self) -> LaunchedScopes {
+        self.scopes.clone()
+    }
+
     /// Create a new client for this launch service.
     pub fn client(&self) -> AppLaunchClient {
         AppLaunchClient {
             send: self.send.clone(),
+            options: Arc::new(LaunchOptions::default()),
         }
     }
 }
@@ -319,15 +770,36 @@ impl AppLaunchService {
 #[derive(Debug, Clone)]
 pub struct AppLaunchClient {
     send: glib::Sender<AppLaunchRequest>,
+    options: Arc<LaunchOptions>,
 }
 
 impl AppLaunchClient {
+    /// Launch apps with the given `options`.
+    pub fn with_options(mut self, options: LaunchOptions) -> Self {
+        self.set_options(options);
+        self
+    }
+
+    /// Replace the options to launch apps with.
+    ///
+    /// See [`AppLaunchClient::with_options`].
+    pub fn set_options(&mut self, options: LaunchOptions) {
+        self.options = Arc::new(options);
+    }
+
     #[instrument(skip(self))]
-    async fn launch(&self, app: AppId, uri: Option<String>) -> Result<(), glib::Error> {
+    async fn launch(
+        &self,
+        app: AppId,
+        uri: Option<String>,
+        command: Option<Vec<OsString>>,
+    ) -> Result<(), glib::Error> {
         let (response_tx, response_rx) = futures_channel::oneshot::channel();
         let request = AppLaunchRequest {
             app,
             uri,
+            options: self.options.clone(),
+            command,
             span: Span::current(),
             response: response_tx,
         };
@@ -349,11 +821,235 @@ impl AppLaunchClient {
 
     /// Launch an app without arguments
     pub async fn launch_app(&self, app: AppId) -> Result<(), glib::Error> {
-        self.launch(app, None).await
+        self.launch(app, None, None).await
     }
 
     /// Launch the app with an URI.
+    ///
+    /// Single-instance apps such as Jetbrains IDEs hand the URI to their running instance if
+    /// any, and exit right away.
     pub async fn launch_uri(&self, app: AppId, uri: String) -> Result<(), glib::Error> {
-        self.launch(app, Some(uri)).await
+        self.launch(app, Some(uri), None).await
+    }
+
+    /// Run `argv` on behalf of `app`, e.g. a command line launcher of the app, to open `uri`.
+    ///
+    /// Run `argv` with the environment of the launch options, but without their extra
+    /// arguments, and move the process to a scope of `app` like launched apps.  Complete once
+    /// the process moved to its scope, without waiting for the process to exit; the launch
+    /// service logs its exit status.  Fail if `argv` can't be run.
+    pub async fn run_command(
+        &self,
+        app: AppId,
+        argv: Vec<OsString>,
+        uri: Option<String>,
+    ) -> Result<(), glib::Error> {
+        self.launch(app, uri, Some(argv)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixStream;
+
+    use parking_lot::Mutex;
+    use pretty_assertions::assert_eq;
+    use zbus::zvariant::OwnedValue;
+    use zbus::{dbus_interface, DBusError};
+
+    use super::*;
+
+    #[derive(DBusError, Debug)]
+    #[dbus_error(prefix = "org.freedesktop.systemd1")]
+    enum MockError {
+        #[dbus_error(zbus_error)]
+        ZBus(zbus::Error),
+        UnitExists(String),
+        UnitMasked(String),
+    }
+
+    /// A systemd manager which fails to start some scopes.
+    #[derive(Default)]
+    struct MockManager {
+        /// Names of existing units.
+        existing: Vec<String>,
+        /// Whether to fail every unit with a masked error.
+        masked: bool,
+        /// The names of all units to start, in order.
+        requested: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[dbus_interface(name = "org.freedesktop.systemd1.Manager")]
+    impl MockManager {
+        fn start_transient_unit(
+            &self,
+            name: String,
+            _mode: String,
+            _properties: Vec<(String, OwnedValue)>,
+            _aux: Vec<(String, Vec<(String, OwnedValue)>)>,
+        ) -> Result<OwnedObjectPath, MockError> {
+            self.requested.lock().push(name.clone());
+            if self.masked {
+                Err(MockError::UnitMasked(format!("Unit {} is masked", name)))
+            } else if self.existing.contains(&name) {
+                Err(MockError::UnitExists(format!(
+                    "Unit {} already exists",
+                    name
+                )))
+            } else {
+                Ok(OwnedObjectPath::try_from("/org/freedesktop/systemd1/unit/test_scope").unwrap())
+            }
+        }
+    }
+
+    /// Move process 42 of `jetbrains-idea.desktop` to a scope of a `manager` on a private bus.
+    ///
+    /// Return the result, and the names of all scopes requested from `manager`.
+    fn move_to_mock_scope(
+        manager: MockManager,
+        mode: ScopeMode,
+    ) -> (zbus::Result<(String, OwnedObjectPath)>, Vec<String>) {
+        let requested = manager.requested.clone();
+        let settings = SystemdScopeSettings {
+            mode,
+            prefix: "app-test".to_string(),
+            started_by: "test".to_string(),
+            documentation: Vec::new(),
+            resources: ScopeResources::default(),
+            app_resources: HashMap::new(),
+        };
+        let result = glib::MainContext::new().block_on(async move {
+            let guid = zbus::Guid::generate();
+            let (server, client) = UnixStream::pair()?;
+            let server = zbus::ConnectionBuilder::unix_stream(server)
+                .server(&guid)
+                .p2p()
+                .serve_at("/org/freedesktop/systemd1", manager)?
+                .build();
+            let client = zbus::ConnectionBuilder::unix_stream(client).p2p().build();
+            let (_server, client) = futures_util::try_join!(server, client)?;
+            let systemd = Systemd1ManagerProxy::builder(&client)
+                .cache_properties(CacheProperties::No)
+                .build()
+                .await?;
+            move_launched_process_to_scope(
+                &systemd,
+                "jetbrains-idea.desktop",
+                "IntelliJ IDEA started by test",
+                &settings,
+                42,
+            )
+            .await
+        });
+        let requested = requested.lock().clone();
+        (result, requested)
+    }
+
+    #[test]
+    fn start_scope() {
+        let (result, requested) = move_to_mock_scope(MockManager::default(), ScopeMode::Fallback);
+        assert_eq!(result.unwrap().0, "app-test-jetbrains\\x2didea-42.scope");
+        assert_eq!(requested, vec!["app-test-jetbrains\\x2didea-42.scope"]);
+    }
+
+    #[test]
+    fn fail_if_scope_exists() {
+        let manager = MockManager {
+            existing: vec!["app-test-jetbrains\\x2didea-42.scope".to_string()],
+            ..MockManager::default()
+        };
+        let (result, requested) = move_to_mock_scope(manager, ScopeMode::Fallback);
+        assert!(is_unit_exists_error(&result.unwrap_err()));
+        assert_eq!(requested.len(), 1);
+    }
+
+    #[test]
+    fn retry_with_unique_name_if_scope_exists() {
+        let manager = MockManager {
+            existing: vec![
+                "app-test-jetbrains\\x2didea-42.scope".to_string(),
+                "app-test-jetbrains\\x2didea-42-1.scope".to_string(),
+            ],
+            ..MockManager::default()
+        };
+        let (result, requested) = move_to_mock_scope(manager, ScopeMode::Retry);
+        assert_eq!(result.unwrap().0, "app-test-jetbrains\\x2didea-42-2.scope");
+        assert_eq!(requested.len(), 3);
+    }
+
+    #[test]
+    fn give_up_after_max_attempts() {
+        let manager = MockManager {
+            existing: (0..MAX_SCOPE_ATTEMPTS)
+                .map(|attempt| match attempt {
+                    0 => "app-test-jetbrains\\x2didea-42.scope".to_string(),
+                    n => format!("app-test-jetbrains\\x2didea-42-{}.scope", n),
+                })
+                .collect(),
+            ..MockManager::default()
+        };
+        let (result, requested) = move_to_mock_scope(manager, ScopeMode::Retry);
+        assert!(is_unit_exists_error(&result.unwrap_err()));
+        assert_eq!(requested.len(), MAX_SCOPE_ATTEMPTS as usize);
+    }
+
+    #[test]
+    fn do_not_retry_other_errors() {
+        let manager = MockManager {
+            masked: true,
+            ..MockManager::default()
+        };
+        let (result, requested) = move_to_mock_scope(manager, ScopeMode::Retry);
+        let error = result.unwrap_err();
+        assert!(!is_unit_exists_error(&error));
+        assert!(
+            matches!(&error, zbus::Error::MethodError(name, _, _) if name.as_str() == "org.freedesktop.systemd1.UnitMasked"),
+            "Unexpected error: {:?}",
+            error
+        );
+        assert_eq!(requested.len(), 1);
+    }
+
+    #[test]
+    fn insert_args_after_program() {
+        let args = vec![
+            "-Dawt.toolkit.name=WLToolkit".to_string(),
+            "a b".to_string(),
+        ];
+        assert_eq!(
+            insert_args("\"/opt/idea/bin/idea.sh\" %u", &args).unwrap(),
+            "'/opt/idea/bin/idea.sh' '-Dawt.toolkit.name=WLToolkit' 'a b' %u"
+        );
+        assert_eq!(insert_args("idea", &[]).unwrap(), "'idea'");
+        assert!(insert_args("", &args).is_err());
+    }
+
+    #[test]
+    fn escape_percent_in_args() {
+        let args = vec!["-XX:MaxRAMPercentage=75%".to_string(), "%u".to_string()];
+        assert_eq!(
+            insert_args("idea %u", &args).unwrap(),
+            "'idea' '-XX:MaxRAMPercentage=75%%' '%%u' %u"
+        );
+    }
+
+    #[test]
+    fn wait_for_pending_launches() {
+        let context = glib::MainContext::new();
+        context.with_thread_default(|| {
+            let pending = PendingLaunches::default();
+            assert!(context.block_on(pending.wait(Duration::from_millis(10))));
+
+            let launch = pending.start();
+            assert_eq!(pending.count(), 1);
+            assert!(!context.block_on(pending.wait(Duration::from_millis(10))));
+
+            context.spawn_local(async move {
+                glib::timeout_future(Duration::from_millis(20)).await;
+                drop(launch);
+            });
+            assert!(context.block_on(pending.wait(Duration::from_secs(5))));
+            assert_eq!(pending.count(), 0);
+        });
     }
 }
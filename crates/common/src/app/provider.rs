@@ -7,25 +7,227 @@
 //! Search providers for apps.
 
 use std::collections::HashMap;
-use std::fmt::Debug;
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use indexmap::IndexMap;
+use futures_util::future::{select, BoxFuture, Either, FutureExt, Shared};
+use futures_util::stream::StreamExt;
+use parking_lot::Mutex;
 use tracing::field;
 use tracing::instrument;
+use tracing_futures::Instrument;
 use zbus::dbus_interface;
 use zbus::zvariant;
 
+use crate::app::snapshot::{ItemsSnapshot, SharedItems};
 use crate::app::*;
+use crate::history::{Activation, ActivationHistory};
+use crate::mainloop::IdleTracker;
 use crate::matching::*;
+use crate::paths::{display_path, home_dir};
 use crate::source::*;
 
+/// Find the URI of the item with the given `id` for `app`.
+///
+/// Look up `id` in `items` first.  If `items` doesn't contain `id`, e.g. because `items` was refreshed
+/// between a search and the activation of one of its results, or because the service restarted in
+/// between, reconstruct the URI from `id` itself, if `id` is a valid item ID for `app`.
+pub(super) fn resolve_item_uri(
+    items: &IdMap<AppLaunchItem>,
+    app: &AppId,
+    id: &str,
+) -> Option<String> {
+    items.get(id).map(|item| item.uri().into()).or_else(|| {
+        debug!(
+            "Item with ID {} not in current items of {}, parsing ID",
+            id, app
+        );
+        match id.parse::<AppItemId>() {
+            Ok(item_id) if item_id.app() == app => Some(item_id.uri().to_string()),
+            Ok(item_id) => {
+                warn!(
+                    "Item ID {} refers to app {}, not {}",
+                    id,
+                    item_id.app(),
+                    app
+                );
+                None
+            }
+            Err(error) => {
+                warn!("Failed to parse item ID {}: {}", id, error);
+                None
+            }
+        }
+    })
+}
+
+/// Loading items from a source slower than this gets logged.
+const SLOW_SOURCE_THRESHOLD: Duration = Duration::from_millis(500);
+
+/// A refresh of items which is shared between all searches waiting for it.
+type Refresh = Shared<BoxFuture<'static, Result<Arc<ItemsSnapshot<AppLaunchItem>>, String>>>;
+
+/// The refresh currently in progress, if any, with the version of the snapshot it loads.
+#[derive(Clone, Default)]
+struct PendingRefresh(Arc<Mutex<Option<(u64, Refresh)>>>);
+
+impl PendingRefresh {
+    /// Get the refresh in progress, if any.
+    fn get(&self) -> Option<Refresh> {
+        self.0.lock().as_ref().map(|(_, refresh)| refresh.clone())
+    }
+}
+
+impl Debug for PendingRefresh {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PendingRefresh")
+            .field(&self.0.lock().as_ref().map(|(version, _)| *version))
+            .finish()
+    }
+}
+
+/// Load items for `version` from `source` and update `items` with the result.
+///
+/// Return the snapshot of the loaded items, or an error message if loading failed.
+async fn refresh_items<S>(
+    app_id: AppId,
+    version: u64,
+    source: Arc<S>,
+    items: SharedItems<AppLaunchItem>,
+) -> Result<Arc<ItemsSnapshot<AppLaunchItem>>, String>
+where
+    S: AsyncItemsSource<AppLaunchItem> + Send + Sync,
+{
+    let start = Instant::now();
+    let result = source.find_recent_items().await;
+    let elapsed = start.elapsed();
+    if SLOW_SOURCE_THRESHOLD < elapsed {
+        warn!("Loading recent items for {} took {:?}", app_id, elapsed);
+    } else {
+        debug!("Loaded recent items for {} in {:?}", app_id, elapsed);
+    }
+    match result {
+        Ok(loaded) => Ok(items.update(version, loaded)),
+        Err(error) => {
+            error!("Failed to update recent items for {}: {:#}", app_id, error);
+            Err(format!(
+                "Failed to update recent items for {}: {:#}",
+                app_id, error
+            ))
+        }
+    }
+}
+
+/// Refresh `items` from `source`.
+///
+/// If `join_pending` is true and a refresh is in progress, return the pending refresh.  Otherwise
+/// start a new refresh on the main context, which supersedes any pending refresh.  The refresh
+/// runs to completion even if nothing waits for it anymore.
+fn start_refresh<S>(
+    app_id: &AppId,
+    source: &Arc<S>,
+    items: &SharedItems<AppLaunchItem>,
+    pending_refresh: &PendingRefresh,
+    join_pending: bool,
+) -> Refresh
+where
+    S: AsyncItemsSource<AppLaunchItem> + Send + Sync + 'static,
+{
+    let mut pending = pending_refresh.0.lock();
+    if let Some((_, refresh)) = pending.as_ref().filter(|_| join_pending) {
+        debug!("Joining pending refresh of items for {}", app_id);
+        return refresh.clone();
+    }
+    let version = items.next_version();
+    let refresh = refresh_items(app_id.clone(), version, source.clone(), items.clone())
+        .in_current_span()
+        .boxed()
+        .shared();
+    *pending = Some((version, refresh.clone()));
+    let pending_refresh = pending_refresh.clone();
+    glib::MainContext::default().spawn(refresh.clone().map(move |_| {
+        let mut pending = pending_refresh.0.lock();
+        // Only clear our own refresh, not a newer one which superseded us
+        if matches!(pending.as_ref(), Some((v, _)) if *v == version) {
+            *pending = None;
+        }
+    }));
+    refresh
+}
+
+/// Refresh `items` from `source` whenever `changes` yields.
+///
+/// Coalesce changes which arrive while a refresh is in progress into a single refresh.  Clear
+/// `watched` once `changes` ends.
+async fn watch_items<S>(
+    app_id: AppId,
+    changes: ItemsChanges,
+    source: Arc<S>,
+    items: SharedItems<AppLaunchItem>,
+    pending_refresh: PendingRefresh,
+    watched: Arc<AtomicBool>,
+) where
+    S: AsyncItemsSource<AppLaunchItem> + Send + Sync + 'static,
+{
+    changes
+        .ready_chunks(64)
+        .for_each(|changes| {
+            debug!(
+                "Items of {} changed {} time(s), refreshing",
+                app_id,
+                changes.len()
+            );
+            start_refresh(&app_id, &source, &items, &pending_refresh, false).map(|_| ())
+        })
+        .await;
+    warn!(
+        "Stopped watching items of {}, refreshing items for every search",
+        app_id
+    );
+    watched.store(false, Ordering::SeqCst);
+}
+
 /// A search provider for recent items.
+///
+/// The provider keeps the items of the last search in a shared snapshot.  All DBus methods only
+/// take `&self`, so searches, sub-searches and result metas of overlapping queries from Gnome Shell
+/// run concurrently, and each call works on a consistent snapshot of items.
+///
+/// Each search refreshes the items from the source.  If the provider has a load timeout, and the
+/// source takes longer, the search returns results from the previous snapshot, and the refresh
+/// continues in the background, for the benefit of subsequent searches.
+///
+/// If the source can watch for changes (see [`AsyncItemsSource::watch`]), the provider refreshes
+/// items on every change instead once it started watching (see
+/// [`AppItemSearchProvider::start_watching`]), and searches only wait for a refresh in progress.
+///
+/// If the provider has an activation history, it records every activated result, and ranks
+/// frequently and recently activated items higher.
+///
+/// If the provider knows the scopes of launched apps, it marks items which are open in a running
+/// app, and hands these items to the running app on activation instead of launching the app.
+///
+/// If the provider knows how to reach a running instance of the app (see [`RunningInstance`]), it
+/// first tries to open activated items in the running instance, and only launches the app if no
+/// instance runs, or if the running instance didn't take the item.
 #[derive(Debug)]
 pub struct AppItemSearchProvider<S: AsyncItemsSource<AppLaunchItem>> {
     launcher: AppLaunchClient,
     app: App,
-    source: S,
-    items: IdMap<AppLaunchItem>,
+    source: Arc<S>,
+    items: SharedItems<AppLaunchItem>,
+    pending_refresh: PendingRefresh,
+    watched: Arc<AtomicBool>,
+    load_timeout: Option<Duration>,
+    roots: Vec<String>,
+    history: Option<Arc<Mutex<ActivationHistory>>>,
+    max_results: Option<usize>,
+    min_score: f64,
+    idle_tracker: Option<IdleTracker>,
+    scopes: Option<LaunchedScopes>,
+    running_instance: Option<Box<dyn RunningInstance>>,
 }
 
 impl<S: AsyncItemsSource<AppLaunchItem>> AppItemSearchProvider<S> {
@@ -33,18 +235,329 @@ impl<S: AsyncItemsSource<AppLaunchItem>> AppItemSearchProvider<S> {
     pub fn app(&self) -> &App {
         &self.app
     }
+
+    /// The number of items this provider currently searches.
+    pub fn item_count(&self) -> usize {
+        self.items.current().items.len()
+    }
 }
 
 impl<S: AsyncItemsSource<AppLaunchItem>> AppItemSearchProvider<S> {
+    /// Whether `query` admits items of the app of this provider.
+    ///
+    /// See [`Query::matches_app`].
+    fn query_matches_app(&self, query: &Query) -> bool {
+        let matches = query.matches_app(&self.app.id().to_string());
+        if !matches {
+            debug!("Query {:?} excludes app {}", query, self.app.id());
+        }
+        matches
+    }
+
+    /// Match paths of items relative to the given `roots`, in addition to the home directory.
+    ///
+    /// Path segments of `roots` then no longer match search terms, so that e.g. a search for `gh`
+    /// doesn't match every project in `~/Code/gh`.
+    pub fn with_roots(mut self, roots: Vec<String>) -> Self {
+        self.roots.extend(roots.iter().map(|root| normalize(root)));
+        self
+    }
+
+    /// Record activated results in `history`, and rank items by their activations.
+    ///
+    /// Providers can share the same history, because item IDs are unique across apps.
+    pub fn with_history(mut self, history: Arc<Mutex<ActivationHistory>>) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Record every call to this provider in `tracker`.
+    pub fn with_idle_tracker(mut self, tracker: IdleTracker) -> Self {
+        self.idle_tracker = Some(tracker);
+        self
+    }
+
+    /// Find items open in a running app with `scopes`.
+    ///
+    /// See [`AppLaunchService::scopes`].
+    pub fn with_launched_scopes(mut self, scopes: LaunchedScopes) -> Self {
+        self.scopes = Some(scopes);
+        self
+    }
+
+    /// Launch the app with extra environment variables and arguments from `options`.
+    pub fn with_launch_options(mut self, options: LaunchOptions) -> Self {
+        self.set_launch_options(options);
+        self
+    }
+
+    /// Find the scope of a running app which has `uri` open, if any.
+    fn find_running_scope(&self, uri: &str) -> Option<LaunchedScope> {
+        self.scopes
+            .as_ref()
+            .and_then(|scopes| scopes.find_running(self.app.id(), uri))
+    }
+
+    /// Open activated items in a running instance of the app through `running_instance`.
+    pub fn with_running_instance<R: RunningInstance + 'static>(
+        mut self,
+        running_instance: R,
+    ) -> Self {
+        self.running_instance = Some(Box::new(running_instance));
+        self
+    }
+
+    /// Try to open `uri` in a running instance of the app.
+    ///
+    /// Return whether a running instance took `uri`.  Fail if an instance runs but didn't take
+    /// `uri`; launching the app again wouldn't help in this case.
+    async fn open_in_running_instance(&self, uri: &str) -> zbus::fdo::Result<bool> {
+        match &self.running_instance {
+            None => Ok(false),
+            Some(running_instance) => match running_instance.open_uri(&self.launcher, uri).await {
+                Ok(true) => {
+                    info!("Opened {} in running instance of {}", uri, self.app.id());
+                    Ok(true)
+                }
+                Ok(false) => {
+                    debug!("No running instance of {} to open {}", self.app.id(), uri);
+                    Ok(false)
+                }
+                Err(error) => {
+                    error!(
+                        "Failed to open {} in running instance of {}: {}",
+                        uri,
+                        self.app.id(),
+                        error
+                    );
+                    Err(zbus::fdo::Error::Failed(format!(
+                        "Failed to open {} in running instance of {}: {}",
+                        uri,
+                        self.app.id(),
+                        error
+                    )))
+                }
+            },
+        }
+    }
+
+    /// Record activity in the idle tracker, if any.
+    fn touch(&self) {
+        if let Some(tracker) = &self.idle_tracker {
+            tracker.touch();
+        }
+    }
+
+    /// Return at most `max_results` results from a search.
+    pub fn with_max_results(mut self, max_results: usize) -> Self {
+        self.max_results = Some(max_results);
+        self
+    }
+
+    /// Discard items whose score is less than `min_score`.
+    ///
+    /// Items whose name matches all search terms score at least 10; items whose path matches
+    /// only score around 1.
+    pub fn with_min_score(mut self, min_score: f64) -> Self {
+        self.min_score = min_score;
+        self
+    }
+
+    /// Find items matching `query` among `items`, ranked by their activations for `terms`.
+    ///
+    /// `query` is the parsed form of `terms`.  Discard items below the minimum score, and return at
+    /// most the maximum number of results.
+    fn find_matching_items<'a, I, Item>(
+        &self,
+        items: I,
+        query: &Query,
+        terms: &[&str],
+    ) -> Vec<&'a str>
+    where
+        I: Iterator<Item = (&'a str, Item)>,
+        Item: ScoreMatchable,
+    {
+        let mut ids = match &self.history {
+            None => find_matching_items_with_boost(items, query, self.min_score, |_| 0.0),
+            Some(history) => {
+                let history = history.lock();
+                find_matching_items_with_boost(items, query, self.min_score, |id| {
+                    history.boost(id, terms)
+                })
+            }
+        };
+        if let Some(max_results) = self.max_results {
+            ids.truncate(max_results);
+        }
+        ids
+    }
+
+    /// Record the activation of the item with the given `id` for `terms`.
+    ///
+    /// Log failures to persist the activation, but otherwise ignore them.
+    async fn record_activation(&self, id: &str, terms: &[&str]) {
+        if let Some(history) = &self.history {
+            // Write outside of the lock, to not block searches on IO.
+            let write = history.lock().record(Activation::now(id, terms));
+            let path = write.path().to_path_buf();
+            if let Err(error) = write.write_async().await {
+                warn!(
+                    "Failed to record activation of {} in {}: {}",
+                    id,
+                    path.display(),
+                    error
+                );
+            }
+        }
+    }
+
+    /// Bound the time a search waits for items from the source to `timeout`.
+    ///
+    /// If loading items takes longer, searches use the items from the last successful load,
+    /// or no items at all, while loading continues in the background.
+    pub fn with_load_timeout(mut self, timeout: Duration) -> Self {
+        self.load_timeout = Some(timeout);
+        self
+    }
+
+    /// Replace the roots of this provider, in addition to the home directory.
+    ///
+    /// See [`AppItemSearchProvider::with_roots`].
+    pub fn set_roots(&mut self, roots: Vec<String>) {
+        self.roots = vec![normalize(&home_dir())];
+        self.roots.extend(roots.iter().map(|root| normalize(root)));
+    }
+
+    /// Replace the load timeout, or wait indefinitely if `timeout` is `None`.
+    ///
+    /// See [`AppItemSearchProvider::with_load_timeout`].
+    pub fn set_load_timeout(&mut self, timeout: Option<Duration>) {
+        self.load_timeout = timeout;
+    }
+
+    /// Replace the maximum number of results, or return all results if `max_results` is `None`.
+    pub fn set_max_results(&mut self, max_results: Option<usize>) {
+        self.max_results = max_results;
+    }
+
+    /// Replace the minimum score of results.
+    ///
+    /// See [`AppItemSearchProvider::with_min_score`].
+    pub fn set_min_score(&mut self, min_score: f64) {
+        self.min_score = min_score;
+    }
+
+    /// Replace the options to launch the app with.
+    ///
+    /// See [`AppItemSearchProvider::with_launch_options`].
+    pub fn set_launch_options(&mut self, options: LaunchOptions) {
+        self.launcher.set_options(options);
+    }
+}
+
+impl<S: AsyncItemsSource<AppLaunchItem> + Send + Sync + 'static> AppItemSearchProvider<S> {
     /// Create a new search provider for recent items of `app`.
     ///
-    /// Uses the given `source` to load recent items.
+    /// Uses the given `source` to load recent items, for every search until the provider starts
+    /// watching `source` for changes (see [`AppItemSearchProvider::start_watching`]).
     pub fn new(app: App, source: S, launcher: AppLaunchClient) -> Self {
         Self {
             launcher,
             app,
-            source,
-            items: IndexMap::new(),
+            source: Arc::new(source),
+            items: SharedItems::default(),
+            pending_refresh: PendingRefresh::default(),
+            watched: Arc::new(AtomicBool::new(false)),
+            load_timeout: None,
+            roots: vec![normalize(&home_dir())],
+            history: None,
+            max_results: None,
+            min_score: 0.0,
+            idle_tracker: None,
+            scopes: None,
+            running_instance: None,
+        }
+    }
+
+    /// Watch the source for changes, and refresh items on every change on `main_context`.
+    ///
+    /// Searches then no longer refresh items, but use the items of the last change.  Return
+    /// whether the source can watch for changes; if not, searches keep refreshing items.
+    ///
+    /// Call this only once.
+    pub fn start_watching(&self, main_context: &glib::MainContext) -> bool {
+        match self.source.watch() {
+            None => {
+                debug!("Items of {} can't be watched for changes", self.app.id());
+                false
+            }
+            Some(changes) => {
+                info!("Watching items of {} for changes", self.app.id());
+                self.watched.store(true, Ordering::SeqCst);
+                main_context.spawn(watch_items(
+                    self.app.id().clone(),
+                    changes,
+                    self.source.clone(),
+                    self.items.clone(),
+                    self.pending_refresh.clone(),
+                    self.watched.clone(),
+                ));
+                true
+            }
+        }
+    }
+
+    /// Refresh items from the source.
+    ///
+    /// Join the pending refresh if any, otherwise start a new refresh on the main context.
+    fn refresh(&self) -> Refresh {
+        start_refresh(
+            self.app.id(),
+            &self.source,
+            &self.items,
+            &self.pending_refresh,
+            true,
+        )
+    }
+
+    /// Wait for `refresh`, but not longer than the load timeout.
+    ///
+    /// If the refresh takes longer, return the current snapshot.
+    async fn wait_for_refresh(
+        &self,
+        refresh: Refresh,
+    ) -> Result<Arc<ItemsSnapshot<AppLaunchItem>>, String> {
+        match self.load_timeout {
+            None => refresh.await,
+            Some(timeout) => match select(refresh, glib::timeout_future(timeout)).await {
+                Either::Left((result, _)) => result,
+                Either::Right(_) => {
+                    let snapshot = self.items.current();
+                    warn!(
+                        "Loading recent items for {} exceeded {:?}, using {} item(s) from snapshot {}",
+                        self.app.id(),
+                        timeout,
+                        snapshot.items.len(),
+                        snapshot.version
+                    );
+                    Ok(snapshot)
+                }
+            },
+        }
+    }
+
+    /// Get up-to-date items for a search.
+    ///
+    /// If the source reports changes and items were loaded already, only wait for a refresh in
+    /// progress, if any.  Otherwise refresh items from the source.
+    async fn items_for_search(&self) -> Result<Arc<ItemsSnapshot<AppLaunchItem>>, String> {
+        if self.watched.load(Ordering::SeqCst) && 0 < self.items.current().version {
+            match self.pending_refresh.get() {
+                Some(refresh) => self.wait_for_refresh(refresh).await,
+                None => Ok(self.items.current()),
+            }
+        } else {
+            self.wait_for_refresh(self.refresh()).await
         }
     }
 }
@@ -60,26 +573,33 @@ impl<S: AsyncItemsSource<AppLaunchItem> + Send + Sync + 'static> AppItemSearchPr
     /// and should return an array of result IDs. gnome-shell will call GetResultMetas for (some) of these result
     /// IDs to get details about the result that can be be displayed in the result list.
     #[instrument(skip(self), fields(app_id = field::debug(self.app.id())))]
-    async fn get_initial_result_set(&mut self, terms: Vec<&str>) -> zbus::fdo::Result<Vec<String>> {
+    async fn get_initial_result_set(&self, terms: Vec<&str>) -> zbus::fdo::Result<Vec<String>> {
+        self.touch();
         debug!("Searching for {:?} of {}", terms, self.app.id());
-        self.items = self.source.find_recent_items().await.map_err(|error| {
-            error!(
-                "Failed to update recent items for {}: {:#}",
-                self.app.id(),
-                error,
-            );
-            zbus::fdo::Error::Failed(format!(
-                "Failed to update recent items for {}: {:#}",
-                self.app.id(),
-                error
-            ))
-        })?;
+        let query = Query::parse(&terms);
+        if !self.query_matches_app(&query) {
+            return Ok(Vec::new());
+        }
+        let snapshot = self
+            .items_for_search()
+            .await
+            .map_err(zbus::fdo::Error::Failed)?;
 
-        let ids = find_matching_items(self.items.iter(), terms.as_slice())
+        let items = snapshot
+            .items
+            .iter()
+            .map(|(id, item)| (id.as_str(), item.relative_to(&self.roots)));
+        let ids = self
+            .find_matching_items(items, &query, &terms)
             .into_iter()
-            .map(String::to_owned)
+            .map(str::to_owned)
             .collect();
-        debug!("Found ids {:?} for {}", ids, self.app.id());
+        debug!(
+            "Found ids {:?} for {} in snapshot {}",
+            ids,
+            self.app.id(),
+            snapshot.version
+        );
         Ok(ids)
     }
 
@@ -94,20 +614,39 @@ impl<S: AsyncItemsSource<AppLaunchItem> + Send + Sync + 'static> AppItemSearchPr
         previous_results: Vec<&str>,
         terms: Vec<&str>,
     ) -> Vec<String> {
+        self.touch();
         debug!(
             "Searching for {:?} in {:?} of {}",
             terms,
             previous_results,
             self.app.id()
         );
-        let candidates = previous_results
-            .iter()
-            .filter_map(|&id| self.items.get(id).map(|p| (id, p)));
-
-        let ids = find_matching_items(candidates, terms.as_slice())
-            .into_iter()
-            .map(|s| s.to_owned())
-            .collect();
+        let query = Query::parse(&terms);
+        if !self.query_matches_app(&query) {
+            return Vec::new();
+        }
+        let snapshot = self.items.current();
+        let ids = if self.max_results.is_some() || query.has_filters() {
+            // The previous results got truncated and may miss items which match the refined
+            // terms better, or stem from terms which qualified or negated terms don't refine,
+            // so search all items again.
+            let candidates = snapshot
+                .items
+                .iter()
+                .map(|(id, item)| (id.as_str(), item.relative_to(&self.roots)));
+            self.find_matching_items(candidates, &query, &terms)
+        } else {
+            let candidates = previous_results.iter().filter_map(|&id| {
+                snapshot
+                    .items
+                    .get(id)
+                    .map(|p| (id, p.relative_to(&self.roots)))
+            });
+            self.find_matching_items(candidates, &query, &terms)
+        }
+        .into_iter()
+        .map(|s| s.to_owned())
+        .collect();
         debug!("Found ids {:?} for {}", ids, self.app.id());
         ids
     }
@@ -128,19 +667,26 @@ impl<S: AsyncItemsSource<AppLaunchItem> + Send + Sync + 'static> AppItemSearchPr
     //  - "description": an optional short description (1-2 lines)
     #[instrument(skip(self), fields(app_id = field::debug(self.app.id())))]
     fn get_result_metas(&self, results: Vec<String>) -> Vec<HashMap<String, zvariant::Value>> {
+        self.touch();
         debug!("Getting meta info for {:?}", results);
+        let snapshot = self.items.current();
         let metas = results
             .iter()
             .filter_map(|id| {
-                self.items.get(id).map(|item| {
+                snapshot.items.get(id).map(|item| {
                     debug!("Compiling meta info for {}", id);
                     debug!("Using icon {} for id {}", self.app.icon(), id);
 
                     let mut meta: HashMap<String, zvariant::Value> = HashMap::new();
                     meta.insert("id".to_string(), id.clone().into());
-                    meta.insert("name".to_string(), (&item.name).into());
+                    meta.insert("name".to_string(), item.name().to_string().into());
                     meta.insert("gicon".to_string(), self.app.icon().to_string().into());
-                    meta.insert("description".to_string(), item.uri.clone().into());
+                    let description = if self.find_running_scope(item.uri()).is_some() {
+                        format!("{} (open)", display_path(item.uri()))
+                    } else {
+                        display_path(item.uri())
+                    };
+                    meta.insert("description".to_string(), description.into());
                     meta
                 })
             })
@@ -155,7 +701,16 @@ impl<S: AsyncItemsSource<AppLaunchItem> + Send + Sync + 'static> AppItemSearchPr
     /// This function is called when the user clicks on an individual result to open it in the application.
     /// The arguments are the result ID, the current search terms and a timestamp.
     ///
-    /// Launches the underlying app with the path to the selected item.
+    /// Launches the underlying app with the path to the selected item.  If the item is no longer
+    /// known, e.g. because the items were refreshed after the search, take the path from the ID.
+    /// If a running instance of the app takes the item, hand the path to the running instance
+    /// instead.  Record the activation in the history once the app or its running instance
+    /// took the item.
+    ///
+    /// Launch the app even if one of its scopes already has the item open: the service can't
+    /// raise windows of other apps, so launching the app again, which then hands the item to its
+    /// running instance, is the only way to focus the item if no [`RunningInstance`] took it.
+    /// The new process gets a scope as well, in case it starts another instance after all.
     #[instrument(skip(self), fields(app_id = field::debug(self.app.id())))]
     async fn activate_result(
         &self,
@@ -163,26 +718,41 @@ impl<S: AsyncItemsSource<AppLaunchItem> + Send + Sync + 'static> AppItemSearchPr
         terms: Vec<&str>,
         timestamp: u32,
     ) -> zbus::fdo::Result<()> {
+        self.touch();
         debug!("Activating result {} for {:?} at {}", id, terms, timestamp);
-        if let Some(item) = self.items.get(id) {
-            info!("Launching recent item {:?} for {}", item, self.app.id());
+        if let Some(uri) = resolve_item_uri(&self.items.current().items, self.app.id(), id) {
+            if self.open_in_running_instance(&uri).await? {
+                self.record_activation(id, &terms).await;
+                return Ok(());
+            }
+            match self.find_running_scope(&uri) {
+                Some(scope) => info!(
+                    "Recent item {} already open in scope {}, launching {} again to focus it",
+                    uri,
+                    scope.name,
+                    self.app.id()
+                ),
+                None => info!("Launching recent item {} for {}", uri, self.app.id()),
+            }
             self.launcher
-                .launch_uri(self.app.id().clone(), item.uri.clone())
+                .launch_uri(self.app.id().clone(), uri.clone())
                 .await
                 .map_err(|error| {
                     error!(
                         "Failed to launch app {} for {:?}: {}",
                         self.app.id(),
-                        item.uri,
+                        uri,
                         error
                     );
                     zbus::fdo::Error::Failed(format!(
                         "Failed to launch app {} for {}: {}",
                         self.app.id(),
-                        item.uri,
+                        uri,
                         error
                     ))
-                })
+                })?;
+            self.record_activation(id, &terms).await;
+            Ok(())
         } else {
             error!("Item with ID {} not found for {}", id, self.app.id());
             Err(zbus::fdo::Error::Failed(format!("Result {} not found", id)))
@@ -197,6 +767,7 @@ impl<S: AsyncItemsSource<AppLaunchItem> + Send + Sync + 'static> AppItemSearchPr
     /// Currently it simply launches the app without any arguments.
     #[instrument(skip(self), fields(app_id = field::debug(self.app.id())))]
     async fn launch_search(&self, _terms: Vec<String>, _timestamp: u32) -> zbus::fdo::Result<()> {
+        self.touch();
         info!("Launching app {} directly", self.app.id());
         self.launcher
             .launch_app(self.app.id().clone())
@@ -211,3 +782,78 @@ impl<S: AsyncItemsSource<AppLaunchItem> + Send + Sync + 'static> AppItemSearchPr
             })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use async_trait::async_trait;
+
+    use super::*;
+
+    /// A source which counts how often it found items, and reports changes from a stream.
+    #[derive(Default)]
+    struct CountingSource {
+        finds: Arc<AtomicUsize>,
+        changes: Mutex<Option<ItemsChanges>>,
+    }
+
+    #[async_trait]
+    impl AsyncItemsSource<AppLaunchItem> for CountingSource {
+        type Err = String;
+
+        async fn find_recent_items(&self) -> Result<IdMap<AppLaunchItem>, Self::Err> {
+            self.finds.fetch_add(1, Ordering::SeqCst);
+            Ok(IdMap::default())
+        }
+
+        fn watch(&self) -> Option<ItemsChanges> {
+            self.changes.lock().take()
+        }
+    }
+
+    fn provider(source: CountingSource) -> AppItemSearchProvider<CountingSource> {
+        AppItemSearchProvider::new(
+            App::new("test.desktop".into(), "test".to_string()),
+            source,
+            AppLaunchService::new().client(),
+        )
+    }
+
+    #[test]
+    fn refresh_items_on_changes_once_watching() {
+        let (send, changes) = futures_channel::mpsc::unbounded();
+        let source = CountingSource {
+            finds: Arc::default(),
+            changes: Mutex::new(Some(changes.boxed())),
+        };
+        let finds = source.finds.clone();
+        let provider = provider(source);
+        assert!(provider.source.changes.lock().is_some());
+        assert!(!provider.watched.load(Ordering::SeqCst));
+
+        // Refreshes run on the default main context
+        let context = glib::MainContext::default();
+        assert!(provider.start_watching(&context));
+        assert!(provider.source.changes.lock().is_none());
+        assert!(provider.watched.load(Ordering::SeqCst));
+
+        send.unbounded_send(()).unwrap();
+        context.block_on(async {
+            for _ in 0..100 {
+                if 0 < finds.load(Ordering::SeqCst) {
+                    break;
+                }
+                glib::timeout_future(Duration::from_millis(10)).await;
+            }
+        });
+        assert_eq!(finds.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn refresh_items_for_every_search_without_changes() {
+        let provider = provider(CountingSource::default());
+        assert!(!provider.start_watching(&glib::MainContext::default()));
+        assert!(!provider.watched.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,37 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Open items in running instances of apps.
+
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+
+use crate::app::AppLaunchClient;
+
+/// An error while opening an item in a running instance.
+pub type RunningInstanceError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Open items in a running instance of an app, instead of launching the app.
+///
+/// Apps which only allow a single instance, such as Jetbrains IDEs, usually have their own way
+/// to find their running instance and to hand it new items, e.g. through a command line tool or
+/// a local socket.
+#[async_trait]
+pub trait RunningInstance: Debug + Send + Sync {
+    /// Open `uri` in a running instance of the app.
+    ///
+    /// Run processes needed to hand over `uri`, e.g. a command line tool of the app, through
+    /// `launcher`, so that they end up in scopes like launched apps.
+    ///
+    /// Return `Ok(true)` if a running instance took `uri`, and `Ok(false)` if no instance of the
+    /// app runs.  Fail if an instance runs, but didn't take `uri`.
+    async fn open_uri(
+        &self,
+        launcher: &AppLaunchClient,
+        uri: &str,
+    ) -> Result<bool, RunningInstanceError>;
+}
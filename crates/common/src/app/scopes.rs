@@ -0,0 +1,275 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Track systemd scopes of launched apps.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures_util::stream::StreamExt;
+use libc::pid_t;
+use parking_lot::Mutex;
+use tracing::{debug, info, instrument, warn};
+use zbus::dbus_interface;
+use zbus::zvariant::OwnedObjectPath;
+
+use crate::app::AppId;
+use crate::systemd::Systemd1UnitProxy;
+
+/// A systemd scope of a launched app.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaunchedScope {
+    /// The name of the scope unit.
+    pub name: String,
+    /// The DBus object path of the scope unit.
+    pub path: OwnedObjectPath,
+    /// The app running in the scope.
+    pub app: AppId,
+    /// The URI the app was launched with, if any.
+    pub uri: Option<String>,
+    /// The process ID of the launched app.
+    pub pid: pid_t,
+    /// The last known active state of the scope, e.g. `active`.
+    pub active_state: String,
+}
+
+impl LaunchedScope {
+    /// Whether the app in this scope still runs.
+    pub fn is_running(&self) -> bool {
+        matches!(
+            self.active_state.as_str(),
+            "active" | "activating" | "reloading"
+        )
+    }
+}
+
+/// Whether a scope in `state` is gone for good.
+///
+/// Scopes of launched apps have `CollectMode=inactive-or-failed`, so systemd unloads them as soon
+/// as they become inactive or fail.
+fn is_final_state(state: &str) -> bool {
+    matches!(state, "inactive" | "failed")
+}
+
+/// The systemd scopes of launched apps, by name of the scope unit.
+///
+/// The launch service adds every scope it creates, and removes scopes once they're gone.
+/// Cloning returns another handle to the same scopes.
+#[derive(Debug, Clone, Default)]
+pub struct LaunchedScopes {
+    scopes: Arc<Mutex<HashMap<String, LaunchedScope>>>,
+}
+
+impl LaunchedScopes {
+    /// Add a new `scope`.
+    pub(super) fn insert(&self, scope: LaunchedScope) {
+        self.scopes.lock().insert(scope.name.clone(), scope);
+    }
+
+    /// Update the active `state` of the scope with the given `name`.
+    ///
+    /// Remove the scope if `state` is final.  Return whether the scope is still tracked.
+    fn update(&self, name: &str, state: String) -> bool {
+        let mut scopes = self.scopes.lock();
+        if is_final_state(&state) {
+            info!("Scope {} became {}, forgetting it", name, state);
+            scopes.remove(name);
+            false
+        } else {
+            match scopes.get_mut(name) {
+                Some(scope) => {
+                    debug!("Scope {} is {}", name, state);
+                    scope.active_state = state;
+                    true
+                }
+                None => false,
+            }
+        }
+    }
+
+    /// Forget the scope with the given `name`.
+    fn remove(&self, name: &str) {
+        self.scopes.lock().remove(name);
+    }
+
+    /// All scopes, ordered by name.
+    pub fn all(&self) -> Vec<LaunchedScope> {
+        let mut scopes: Vec<_> = self.scopes.lock().values().cloned().collect();
+        scopes.sort_by(|a, b| a.name.cmp(&b.name));
+        scopes
+    }
+
+    /// Whether an app still runs in any scope.
+    pub fn any_running(&self) -> bool {
+        self.scopes.lock().values().any(LaunchedScope::is_running)
+    }
+
+    /// Find a scope in which `app` still runs with `uri`.
+    pub fn find_running(&self, app: &AppId, uri: &str) -> Option<LaunchedScope> {
+        self.scopes
+            .lock()
+            .values()
+            .find(|scope| {
+                &scope.app == app && scope.uri.as_deref() == Some(uri) && scope.is_running()
+            })
+            .cloned()
+    }
+}
+
+/// Follow the active state of the scope with the given `name` at `path`.
+///
+/// Subscribe to property changes of the scope unit on `connection` and update `scopes`
+/// accordingly, until the scope is gone.  Forget the scope if its state cannot be tracked.
+#[instrument(skip(connection, scopes))]
+pub(super) async fn track_scope(
+    connection: &zbus::Connection,
+    scopes: &LaunchedScopes,
+    name: &str,
+    path: OwnedObjectPath,
+) {
+    let unit = match Systemd1UnitProxy::builder(connection).path(path) {
+        Ok(builder) => builder.build().await,
+        Err(error) => Err(error),
+    };
+    let unit = match unit {
+        Ok(unit) => unit,
+        Err(error) => {
+            warn!(
+                "Failed to connect to scope {}, not tracking it: {}",
+                name, error
+            );
+            scopes.remove(name);
+            return;
+        }
+    };
+    // Subscribe before getting the initial state, to not miss changes in between.
+    let mut changes = unit.receive_active_state_changed().await;
+    match unit.active_state().await {
+        Ok(state) => {
+            if !scopes.update(name, state) {
+                return;
+            }
+        }
+        Err(error) => {
+            warn!(
+                "Failed to get state of scope {}, not tracking it: {}",
+                name, error
+            );
+            scopes.remove(name);
+            return;
+        }
+    }
+    while let Some(change) = changes.next().await {
+        match change.get().await {
+            Ok(state) => {
+                if !scopes.update(name, state) {
+                    return;
+                }
+            }
+            Err(error) => {
+                warn!(
+                    "Failed to get state of scope {}, not tracking it anymore: {}",
+                    name, error
+                );
+                break;
+            }
+        }
+    }
+    scopes.remove(name);
+}
+
+/// The DBus interface to list scopes of launched apps.
+#[dbus_interface(name = "de.swsnr.searchprovider.LaunchedScopes1")]
+impl LaunchedScopes {
+    /// List all scopes of launched apps.
+    ///
+    /// Return the name of the scope unit, the desktop ID of the app, the URI the app was launched
+    /// with or an empty string, the active state, and the object path of the scope unit for every
+    /// scope.
+    #[dbus_interface(name = "ListScopes")]
+    fn list_scopes(&self) -> Vec<(String, String, String, String, OwnedObjectPath)> {
+        self.all()
+            .into_iter()
+            .map(|scope| {
+                (
+                    scope.name,
+                    scope.app.to_string(),
+                    scope.uri.unwrap_or_default(),
+                    scope.active_state,
+                    scope.path,
+                )
+            })
+            .collect()
+    }
+
+    /// Whether `app` still runs with `uri` in one of its scopes.
+    #[dbus_interface(name = "IsRunning")]
+    fn is_running(&self, app: &str, uri: &str) -> bool {
+        self.find_running(&app.into(), uri).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn scope(name: &str, uri: &str) -> LaunchedScope {
+        LaunchedScope {
+            name: name.to_string(),
+            path: OwnedObjectPath::try_from("/org/freedesktop/systemd1/unit/test_scope").unwrap(),
+            app: "jetbrains-idea.desktop".into(),
+            uri: Some(uri.to_string()),
+            pid: 42,
+            active_state: "active".to_string(),
+        }
+    }
+
+    #[test]
+    fn find_running_scope() {
+        let scopes = LaunchedScopes::default();
+        scopes.insert(scope("a.scope", "/home/foo/mdcat"));
+        scopes.insert(scope("b.scope", "/home/foo/bar"));
+        assert!(scopes.any_running());
+        let app = "jetbrains-idea.desktop".into();
+        assert_eq!(
+            scopes
+                .find_running(&app, "/home/foo/mdcat")
+                .map(|scope| scope.name),
+            Some("a.scope".to_string())
+        );
+        assert!(scopes.find_running(&app, "/home/foo/baz").is_none());
+        assert!(scopes
+            .find_running(&"jetbrains-clion.desktop".into(), "/home/foo/mdcat")
+            .is_none());
+    }
+
+    #[test]
+    fn update_state() {
+        let scopes = LaunchedScopes::default();
+        scopes.insert(scope("a.scope", "/home/foo/mdcat"));
+        let app = "jetbrains-idea.desktop".into();
+        assert!(scopes.update("a.scope", "deactivating".to_string()));
+        assert_eq!(scopes.all()[0].active_state, "deactivating");
+        assert!(scopes.find_running(&app, "/home/foo/mdcat").is_none());
+        assert!(!scopes.any_running());
+        assert!(!scopes.update("b.scope", "active".to_string()));
+        assert!(scopes.all().iter().all(|scope| scope.name != "b.scope"));
+    }
+
+    #[test]
+    fn forget_scope_in_final_state() {
+        let scopes = LaunchedScopes::default();
+        scopes.insert(scope("a.scope", "/home/foo/mdcat"));
+        scopes.insert(scope("b.scope", "/home/foo/bar"));
+        assert!(!scopes.update("a.scope", "inactive".to_string()));
+        assert!(!scopes.update("b.scope", "failed".to_string()));
+        assert!(scopes.all().is_empty());
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Shared, versioned snapshots of recent items.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use tracing::trace;
+
+use crate::source::IdMap;
+
+/// An immutable snapshot of items at a given version.
+#[derive(Debug)]
+pub struct ItemsSnapshot<T> {
+    /// The version of this snapshot.
+    ///
+    /// Later snapshots have greater versions.
+    pub version: u64,
+    /// The items in this snapshot.
+    pub items: IdMap<T>,
+}
+
+/// The current snapshot of items, shared between concurrent readers and writers.
+///
+/// Readers obtain the current snapshot and keep working with it, while writers concurrently
+/// swap in newer snapshots; the lock is only ever held for swapping the snapshot pointer, and
+/// never while loading items or matching them.
+///
+/// Cloning returns another handle to the same shared state.
+#[derive(Debug)]
+pub struct SharedItems<T> {
+    /// The current snapshot.
+    current: Arc<RwLock<Arc<ItemsSnapshot<T>>>>,
+    /// The next version to hand out.
+    next_version: Arc<AtomicU64>,
+}
+
+impl<T> Clone for SharedItems<T> {
+    fn clone(&self) -> Self {
+        Self {
+            current: self.current.clone(),
+            next_version: self.next_version.clone(),
+        }
+    }
+}
+
+impl<T> Default for SharedItems<T> {
+    fn default() -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Arc::new(ItemsSnapshot {
+                version: 0,
+                items: IdMap::new(),
+            }))),
+            next_version: Arc::new(AtomicU64::new(1)),
+        }
+    }
+}
+
+impl<T> SharedItems<T> {
+    /// Get the current snapshot.
+    pub fn current(&self) -> Arc<ItemsSnapshot<T>> {
+        self.current.read().clone()
+    }
+
+    /// Reserve a version for a new snapshot.
+    ///
+    /// Call this before starting to load items, and pass the version to [`SharedItems::update`]
+    /// afterwards, so that items from a load which started earlier never replace items from a
+    /// load which started later but finished first.
+    pub fn next_version(&self) -> u64 {
+        self.next_version.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// Update the snapshot with `items` loaded for `version`.
+    ///
+    /// If the current snapshot is newer than `version` leave it untouched.
+    ///
+    /// Return the snapshot of the `items`, regardless of whether it became the current snapshot.
+    pub fn update(&self, version: u64, items: IdMap<T>) -> Arc<ItemsSnapshot<T>> {
+        let snapshot = Arc::new(ItemsSnapshot { version, items });
+        let mut current = self.current.write();
+        if current.version < version {
+            *current = snapshot.clone();
+        } else {
+            trace!(
+                "Not replacing snapshot at version {} with older version {}",
+                current.version,
+                version
+            );
+        }
+        snapshot
+    }
+}
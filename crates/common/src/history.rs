@@ -0,0 +1,467 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A persistent history of activated items, to rank frequently and recently used items higher.
+
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::{debug, trace, warn};
+
+use crate::matching::normalize;
+
+/// The maximum number of activations to keep.
+///
+/// Recording an activation drops the oldest activations beyond this number from memory, and
+/// loading the history also drops them from the history file.
+const MAX_ACTIVATIONS: usize = 1000;
+
+/// The half life of an activation in seconds.
+///
+/// An activation from a week ago counts half as much as an activation from now.
+const HALF_LIFE_SECS: f64 = 7.0 * 24.0 * 60.0 * 60.0;
+
+/// The maximum boost of a frequently and recently activated item.
+///
+/// This is less than the score of a single term matching the name of an item (see
+/// [`crate::app::AppLaunchItem`]), so that the history never lifts an item which doesn't match by
+/// name above an item which does.  It does however exceed the scores of path and keyword matches,
+/// so frequently activated items which only match by keyword can outrank items which match by
+/// path.
+const MAX_BOOST: f64 = 5.0;
+
+/// A single activation of an item.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Activation {
+    /// When the item was activated, in seconds since the epoch.
+    pub timestamp: u64,
+    /// The ID of the activated item.
+    pub id: String,
+    /// The search terms which found the item.
+    pub terms: Vec<String>,
+}
+
+impl Activation {
+    /// Create a new activation of the item `id` for `terms` at the current time.
+    pub fn now<S: AsRef<str>>(id: &str, terms: &[S]) -> Self {
+        Self {
+            timestamp: now(),
+            id: id.to_string(),
+            terms: terms.iter().map(|s| s.as_ref().to_string()).collect(),
+        }
+    }
+
+    /// Serialize this activation to a line, without trailing newline.
+    fn to_line(&self) -> String {
+        format!("{}\t{}\t{}", self.timestamp, self.id, self.terms.join(" "))
+    }
+
+    /// Parse an activation from a `line`.
+    fn from_line(line: &str) -> Option<Self> {
+        let mut parts = line.splitn(3, '\t');
+        let timestamp = parts.next()?.parse().ok()?;
+        let id = parts.next().filter(|s| !s.is_empty())?.to_string();
+        let terms = parts
+            .next()
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(ToString::to_string)
+            .collect();
+        Some(Self {
+            timestamp,
+            id,
+            terms,
+        })
+    }
+
+    /// Whether this activation matches `terms`.
+    ///
+    /// This is the case if every term is a prefix of any term of this activation.
+    fn matches_terms<S: AsRef<str>>(&self, terms: &[S]) -> bool {
+        !terms.is_empty()
+            && terms.iter().all(|term| {
+                let term = normalize(term.as_ref());
+                self.terms.iter().any(|t| normalize(t).starts_with(&term))
+            })
+    }
+}
+
+/// The current time in seconds since the epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// A pending write of a recorded activation to the history file.
+///
+/// See [`ActivationHistory::record`].
+#[derive(Debug)]
+#[must_use = "The activation isn't persisted until written"]
+pub struct HistoryWrite {
+    /// The history file.
+    path: PathBuf,
+    /// The line to append to the history file.
+    line: String,
+}
+
+impl HistoryWrite {
+    /// The path of the file to write to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append the activation to the history file.
+    pub fn write(self) -> std::io::Result<()> {
+        if let Some(directory) = self.path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", self.line)
+    }
+
+    /// Append the activation to the history file on a separate thread.
+    ///
+    /// Unlike [`HistoryWrite::write`] this doesn't block the main loop on IO.
+    pub async fn write_async(self) -> std::io::Result<()> {
+        let (send, recv) = futures_channel::oneshot::channel();
+        std::thread::spawn(move || {
+            // We don't care if the receiver went away
+            let _ = send.send(self.write());
+        });
+        recv.await.unwrap_or_else(|_| {
+            Err(std::io::Error::new(
+                ErrorKind::Other,
+                "Thread writing the history went away",
+            ))
+        })
+    }
+}
+
+/// Replace the contents of the history file at `path` with `activations`.
+///
+/// Write to a temporary file first and move it to `path` afterwards, so that `path` always
+/// holds a complete history.
+fn rewrite_history_file(path: &Path, activations: &[Activation]) -> std::io::Result<()> {
+    let mut contents = String::new();
+    for activation in activations {
+        contents.push_str(&activation.to_line());
+        contents.push('\n');
+    }
+    let temp_path = path.with_extension("tmp");
+    std::fs::write(&temp_path, contents)?;
+    std::fs::rename(&temp_path, path)
+}
+
+/// A history of activated items, persisted in a file.
+///
+/// The file contains one activation per line.  Recording activations appends to this file, and
+/// loading the history compacts the file if it holds more than [`MAX_ACTIVATIONS`] lines.
+#[derive(Debug)]
+pub struct ActivationHistory {
+    /// The file to persist activations in.
+    path: PathBuf,
+    /// All activations in this history, from oldest to newest.
+    activations: Vec<Activation>,
+}
+
+impl ActivationHistory {
+    /// The default location of the history for the service `name`.
+    ///
+    /// This is `activations` in the directory for `name` in `$XDG_STATE_HOME`.
+    pub fn default_path(name: &str) -> PathBuf {
+        crate::paths::user_state_dir()
+            .join(name)
+            .join("activations")
+    }
+
+    /// Load the history from the file at `path`.
+    ///
+    /// If `path` doesn't exist return an empty history.  Skip invalid lines in the file.
+    ///
+    /// Rewrite the file with only the latest activations if it got too long.
+    pub fn load(path: PathBuf) -> std::io::Result<Self> {
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                debug!("No activation history at {}", path.display());
+                String::new()
+            }
+            Err(error) => return Err(error),
+        };
+        let mut activations: Vec<Activation> = contents
+            .lines()
+            .filter_map(|line| {
+                let activation = Activation::from_line(line);
+                if activation.is_none() {
+                    warn!("Skipping invalid line in {}: {}", path.display(), line);
+                }
+                activation
+            })
+            .collect();
+        if MAX_ACTIVATIONS < activations.len() {
+            activations.drain(..activations.len() - MAX_ACTIVATIONS);
+        }
+        if MAX_ACTIVATIONS < contents.lines().count() {
+            debug!(
+                "Compacting {} to {} activation(s)",
+                path.display(),
+                activations.len()
+            );
+            if let Err(error) = rewrite_history_file(&path, &activations) {
+                warn!("Failed to compact {}: {}", path.display(), error);
+            }
+        }
+        debug!(
+            "Loaded {} activation(s) from {}",
+            activations.len(),
+            path.display()
+        );
+        Ok(Self { path, activations })
+    }
+
+    /// The path of the file this history persists activations in.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// All activations, from oldest to newest.
+    pub fn activations(&self) -> &[Activation] {
+        &self.activations
+    }
+
+    /// Record a new `activation`, and drop the oldest activations beyond the maximum.
+    ///
+    /// Return the write which appends `activation` to the history file.  Callers which share
+    /// the history should release it before writing, so as to not block other users of the
+    /// history while writing.
+    pub fn record(&mut self, activation: Activation) -> HistoryWrite {
+        trace!("Recording activation {:?}", activation);
+        let write = HistoryWrite {
+            path: self.path.clone(),
+            line: activation.to_line(),
+        };
+        self.activations.push(activation);
+        if MAX_ACTIVATIONS < self.activations.len() {
+            self.activations
+                .drain(..self.activations.len() - MAX_ACTIVATIONS);
+        }
+        write
+    }
+
+    /// Load activations from the history file again.
+    ///
+    /// Pick up changes from other processes, e.g. `--clear-history`.
+    pub fn reload(&mut self) -> std::io::Result<()> {
+        *self = Self::load(self.path.clone())?;
+        Ok(())
+    }
+
+    /// Clear all activations, and delete the history file.
+    ///
+    /// This doesn't affect other processes which loaded the same history file; they need to
+    /// [`reload`](ActivationHistory::reload).
+    pub fn clear(&mut self) -> std::io::Result<()> {
+        self.activations.clear();
+        match std::fs::remove_file(&self.path) {
+            Err(error) if error.kind() != ErrorKind::NotFound => Err(error),
+            _ => Ok(()),
+        }
+    }
+
+    /// Compute the boost for the item `id` found by `terms` at time `now`.
+    ///
+    /// Every activation of `id` contributes to the boost, decaying with age; activations for
+    /// the same terms count twice.  The boost never exceeds 5.
+    pub fn boost_at<S: AsRef<str>>(&self, id: &str, terms: &[S], now: u64) -> f64 {
+        let boost: f64 = self
+            .activations
+            .iter()
+            .filter(|activation| activation.id == id)
+            .map(|activation| {
+                let age = now.saturating_sub(activation.timestamp) as f64;
+                let weight = if activation.matches_terms(terms) {
+                    2.0
+                } else {
+                    1.0
+                };
+                weight * 0.5_f64.powf(age / HALF_LIFE_SECS)
+            })
+            .sum();
+        // Scale logarithmically, so that items activated very often don't run away
+        (boost.ln_1p()).min(MAX_BOOST)
+    }
+
+    /// Compute the boost for the item `id` found by `terms` now.
+    ///
+    /// See [`ActivationHistory::boost_at`].
+    pub fn boost<S: AsRef<str>>(&self, id: &str, terms: &[S]) -> f64 {
+        self.boost_at(id, terms, now())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::testutil::{block_on, TempDir};
+
+    use super::*;
+
+    pub(crate) fn activation(timestamp: u64, id: &str, terms: &[&str]) -> Activation {
+        Activation {
+            timestamp,
+            id: id.to_string(),
+            terms: terms.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    pub(crate) fn history(activations: Vec<Activation>) -> ActivationHistory {
+        ActivationHistory {
+            path: PathBuf::from("/nonexistent"),
+            activations,
+        }
+    }
+
+    #[test]
+    fn roundtrip_line() {
+        let activation = activation(1234, "app-item:v1:foo:bar", &["foo", "bar"]);
+        assert_eq!(
+            Activation::from_line(&activation.to_line()),
+            Some(activation)
+        );
+        assert_eq!(
+            Activation::from_line("1234\tapp-item:v1:foo:bar\t"),
+            Some(Activation {
+                timestamp: 1234,
+                id: "app-item:v1:foo:bar".to_string(),
+                terms: Vec::new()
+            })
+        );
+        assert_eq!(Activation::from_line("soon\tfoo\tbar"), None);
+        assert_eq!(Activation::from_line("1234"), None);
+    }
+
+    #[test]
+    fn boost_frequent_items_more() {
+        let now = 100_000;
+        let history = history(vec![
+            activation(now, "1", &["foo"]),
+            activation(now, "1", &["foo"]),
+            activation(now, "2", &["foo"]),
+        ]);
+        assert!(history.boost_at("2", &["foo"], now) < history.boost_at("1", &["foo"], now));
+        assert_eq!(history.boost_at("3", &["foo"], now), 0.0);
+    }
+
+    #[test]
+    fn boost_recent_items_more() {
+        let now = 10_000_000;
+        let history = history(vec![
+            activation(now - 30 * 24 * 60 * 60, "1", &["foo"]),
+            activation(now - 60, "2", &["foo"]),
+        ]);
+        assert!(history.boost_at("1", &["foo"], now) < history.boost_at("2", &["foo"], now));
+    }
+
+    #[test]
+    fn boost_items_for_same_terms_more() {
+        let now = 100_000;
+        let history = history(vec![
+            activation(now, "1", &["mdcat"]),
+            activation(now, "2", &["other"]),
+        ]);
+        assert!(history.boost_at("2", &["md"], now) < history.boost_at("1", &["md"], now));
+    }
+
+    #[test]
+    fn boost_is_bounded() {
+        let now = 100_000;
+        let history = history((0..1000).map(|_| activation(now, "1", &["foo"])).collect());
+        assert_eq!(history.boost_at("1", &["foo"], now), MAX_BOOST);
+    }
+
+    #[test]
+    fn record_load_and_clear() {
+        let directory = TempDir::new();
+        let path = directory.path().join("state").join("activations");
+        let mut history = ActivationHistory::load(path.clone()).unwrap();
+        assert!(history.activations().is_empty());
+
+        history
+            .record(activation(1, "1", &["foo"]))
+            .write()
+            .unwrap();
+        history
+            .record(activation(2, "2", &["bar"]))
+            .write()
+            .unwrap();
+        let loaded = ActivationHistory::load(path.clone()).unwrap();
+        assert_eq!(
+            loaded.activations(),
+            &[activation(1, "1", &["foo"]), activation(2, "2", &["bar"])]
+        );
+
+        let mut other = ActivationHistory::load(path.clone()).unwrap();
+        history.clear().unwrap();
+        assert!(!path.exists());
+        assert_eq!(other.activations().len(), 2);
+        other.reload().unwrap();
+        assert!(other.activations().is_empty());
+        assert!(ActivationHistory::load(path)
+            .unwrap()
+            .activations()
+            .is_empty());
+    }
+
+    #[test]
+    fn write_async() {
+        let directory = TempDir::new();
+        let path = directory.path().join("state").join("activations");
+        let mut history = ActivationHistory::load(path.clone()).unwrap();
+        block_on(history.record(activation(1, "1", &["foo"])).write_async()).unwrap();
+        assert_eq!(
+            ActivationHistory::load(path).unwrap().activations(),
+            &[activation(1, "1", &["foo"])]
+        );
+    }
+
+    #[test]
+    fn trim_recorded_activations() {
+        let mut history = history(Vec::new());
+        for timestamp in 0..(MAX_ACTIVATIONS as u64 + 10) {
+            // Don't write to the nonexistent history file
+            let _ = history.record(activation(timestamp, "1", &["foo"]));
+        }
+        assert_eq!(history.activations().len(), MAX_ACTIVATIONS);
+        assert_eq!(history.activations()[0].timestamp, 10);
+    }
+
+    #[test]
+    fn compact_history_file_on_load() {
+        let directory = TempDir::new();
+        let path = directory.path().join("activations");
+        let mut history = ActivationHistory::load(path.clone()).unwrap();
+        for timestamp in 0..(MAX_ACTIVATIONS as u64 + 10) {
+            history
+                .record(activation(timestamp, "1", &["foo"]))
+                .write()
+                .unwrap();
+        }
+        let lines = |path: &Path| std::fs::read_to_string(path).unwrap().lines().count();
+        assert_eq!(lines(&path), MAX_ACTIVATIONS + 10);
+
+        let loaded = ActivationHistory::load(path.clone()).unwrap();
+        assert_eq!(loaded.activations(), history.activations());
+        assert_eq!(lines(&path), MAX_ACTIVATIONS);
+    }
+}
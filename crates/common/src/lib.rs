@@ -11,10 +11,15 @@
 mod logcontrol;
 
 pub mod app;
+pub mod history;
 pub mod logging;
 pub mod mainloop;
 pub mod matching;
+pub mod notify;
+pub mod paths;
 pub mod source;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testutil;
 
 mod systemd;
 
@@ -24,4 +29,5 @@ pub use futures_channel;
 pub use futures_util;
 pub use gio;
 pub use glib;
+pub use parking_lot;
 pub use zbus;
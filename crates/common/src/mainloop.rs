@@ -6,31 +6,112 @@
 
 //! Mainloop utilities for dbus serch providers.
 
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures_util::future::{select, FutureExt};
+use futures_util::stream::Stream;
+use parking_lot::Mutex;
 use tracing::{debug, trace};
 
-/// Create a simple main loop on `context`.
-pub fn create_main_loop(context: &glib::MainContext) -> glib::MainLoop {
-    let mainloop = glib::MainLoop::new(Some(context), false);
-
-    trace!("Listening for SIGTERM");
-    glib::source::unix_signal_add(
-        libc::SIGTERM,
-        glib::clone!(@strong mainloop =>  move || {
-            debug!("Terminated, quitting mainloop");
-            mainloop.quit();
-            glib::Continue(false)
-        }),
-    );
-
-    trace!("Listening for SIGINT");
-    glib::source::unix_signal_add(
-        libc::SIGINT,
-        glib::clone!(@strong mainloop =>  move || {
-            debug!("Interrupted, quitting mainloop");
-            mainloop.quit();
-            glib::Continue(false)
-        }),
-    );
-
-    mainloop
+/// Tracks activity of a service, to tell when it became idle.
+///
+/// Cloning returns another handle to the same tracker.
+#[derive(Debug, Clone)]
+pub struct IdleTracker {
+    last_activity: Arc<Mutex<Instant>>,
+}
+
+impl Default for IdleTracker {
+    fn default() -> Self {
+        Self {
+            last_activity: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+}
+
+impl IdleTracker {
+    /// Create a new tracker, with activity right now.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record activity right now.
+    pub fn touch(&self) {
+        *self.last_activity.lock() = Instant::now();
+    }
+
+    /// How long ago the last activity was.
+    pub fn idle_time(&self) -> Duration {
+        self.last_activity.lock().elapsed()
+    }
+}
+
+/// Wait until `tracker` saw no activity for `timeout`.
+///
+/// Uses Glib timeouts, so the returned future must run on a Glib main context.
+pub async fn wait_until_idle(tracker: &IdleTracker, timeout: Duration) {
+    loop {
+        let idle_time = tracker.idle_time();
+        if timeout <= idle_time {
+            debug!("Idle for {:?}", idle_time);
+            return;
+        }
+        trace!(
+            "Idle for {:?}, waiting {:?}",
+            idle_time,
+            timeout - idle_time
+        );
+        glib::timeout_future(timeout - idle_time).await;
+    }
+}
+
+/// Wait until the process receives SIGTERM or SIGINT.
+///
+/// Uses Glib signal sources, so the returned future must run on a Glib main context.
+pub async fn wait_for_termination() {
+    let sigterm = glib::unix_signal_future(libc::SIGTERM).map(|_| "SIGTERM");
+    let sigint = glib::unix_signal_future(libc::SIGINT).map(|_| "SIGINT");
+    let (signal, _) = select(sigterm, sigint).await.factor_first();
+    debug!("Received {}", signal);
+}
+
+/// Get a stream of SIGHUP signals.
+///
+/// Services conventionally reload their configuration on SIGHUP.  Uses Glib signal sources,
+/// so the stream must be polled on a Glib main context.
+pub fn hangup_signals() -> impl Stream<Item = ()> {
+    glib::unix_signal_stream(libc::SIGHUP)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::block_on;
+
+    use super::*;
+
+    #[test]
+    fn idle_after_timeout() {
+        let tracker = IdleTracker::new();
+        let start = Instant::now();
+        block_on(wait_until_idle(&tracker, Duration::from_millis(50)));
+        assert!(Duration::from_millis(50) <= start.elapsed());
+        assert!(Duration::from_millis(50) <= tracker.idle_time());
+    }
+
+    #[test]
+    fn activity_postpones_idle() {
+        let tracker = IdleTracker::new();
+        let start = Instant::now();
+        let context = glib::MainContext::new();
+        context.with_thread_default(|| {
+            let toucher = tracker.clone();
+            context.spawn_local(async move {
+                glib::timeout_future(Duration::from_millis(30)).await;
+                toucher.touch();
+            });
+            context.block_on(wait_until_idle(&tracker, Duration::from_millis(50)));
+        });
+        assert!(Duration::from_millis(80) <= start.elapsed());
+    }
 }
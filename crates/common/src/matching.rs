@@ -12,45 +12,73 @@ use tracing::trace;
 
 pub use indexmap::IndexMap;
 
-/// Match against a list of terms and return a score.
+mod normalize;
+mod path;
+mod query;
+
+pub use normalize::{normalize, rfind_chars};
+pub use path::{component_score, position_score};
+pub use query::{Query, QueryField, QueryTerm};
+
+/// Match against a search query and return a score.
 pub trait ScoreMatchable {
-    /// Match self against `terms` and return a score about how "well" self matches `terms`.
+    /// Match self against `query` and return a score about how "well" self matches `query`.
     ///
-    /// A score of 0 or less denotes that `self` doesn't match `terms`; a score greater than zero indicates
+    /// A score of 0 or less denotes that `self` doesn't match `query`; a score greater than zero indicates
     /// a match.
     ///
-    /// The higher the score the better self matches `terms`; as a rule of thumb a score of 100 should be
+    /// The higher the score the better self matches `query`; as a rule of thumb a score of 100 should be
     /// considered a perfect match.
-    fn match_score<S: AsRef<str>>(&self, terms: &[S]) -> f64;
+    fn match_score(&self, query: &Query) -> f64;
 }
 
 impl<'a, T> ScoreMatchable for &'a T
 where
     T: ScoreMatchable,
 {
-    fn match_score<S: AsRef<str>>(&self, terms: &[S]) -> f64 {
-        (*self).match_score(terms)
+    fn match_score(&self, query: &Query) -> f64 {
+        (*self).match_score(query)
     }
 }
 
-/// Find all items from `items` which match the given `terms`.
+/// Find all items from `items` which match the given `query`.
 ///
-/// `items` is an iterator over pairs of `(id, item)`.
+/// `items` is an iterator over pairs of `(id, item)`.  Parse `query` once per search with
+/// [`Query::parse`], and not for every item.
 ///
 /// For each item compute the score with `MatchScore`; discard projects with zero score,
 /// and return a list of item IDs with non-zero score, ordered by score in descending order.
-pub fn find_matching_items<'a, I, T, K, Item>(items: I, terms: &'a [T]) -> Vec<K>
+pub fn find_matching_items<I, K, Item>(items: I, query: &Query) -> Vec<K>
+where
+    I: Iterator<Item = (K, Item)>,
+    Item: ScoreMatchable,
+    K: Debug,
+{
+    find_matching_items_with_boost(items, query, 0.0, |_| 0.0)
+}
+
+/// Find all items from `items` which match the given `terms`, and boost their scores.
+///
+/// Like [`find_matching_items`], but discard items whose score is less than `min_score`, and add
+/// `boost(id)` to the score of every remaining item before ordering.  The boost never makes an
+/// item match which doesn't match `terms` well enough on its own.
+pub fn find_matching_items_with_boost<I, K, Item, B>(
+    items: I,
+    query: &Query,
+    min_score: f64,
+    boost: B,
+) -> Vec<K>
 where
-    I: Iterator<Item = (K, Item)> + 'a,
+    I: Iterator<Item = (K, Item)>,
     Item: ScoreMatchable,
-    T: AsRef<str>,
     K: Debug,
+    B: Fn(&K) -> f64,
 {
     let mut matches: Vec<(f64, K)> = items
-        .filter_map(move |(id, item)| {
-            let score = item.match_score(terms);
-            if 0.0 < score {
-                Some((score, id))
+        .filter_map(|(id, item)| {
+            let score = item.match_score(query);
+            if 0.0 < score && min_score <= score {
+                Some((score + boost(&id), id))
             } else {
                 None
             }
@@ -58,10 +86,6 @@ where
         .collect();
     // Sort by score, descending
     matches.sort_by(|(score_a, _), (score_b, _)| score_b.partial_cmp(score_a).unwrap());
-    trace!(
-        "Matches {:?} for terms {:?}",
-        matches,
-        terms.iter().map(|s| s.as_ref()).collect::<Vec<&str>>()
-    );
-    matches.into_iter().map(move |(_, id)| id).collect()
+    trace!("Matches {:?} for query {:?}", matches, query);
+    matches.into_iter().map(|(_, id)| id).collect()
 }
@@ -0,0 +1,126 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Normalization of text for matching.
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Whether `c` is a combining diacritic of Latin, Greek or Cyrillic script.
+///
+/// Other combining marks, e.g. the voiced sound marks of Japanese kana, distinguish letters
+/// rather than decorate them, so they must not be stripped.
+fn is_diacritic(c: char) -> bool {
+    matches!(c,
+        // Combining Diacritical Marks
+        '\u{0300}'..='\u{036F}'
+        // Combining Cyrillic marks
+        | '\u{0483}'..='\u{0489}'
+        // Combining Diacritical Marks Extended
+        | '\u{1AB0}'..='\u{1AFF}'
+        // Combining Diacritical Marks Supplement
+        | '\u{1DC0}'..='\u{1DFF}'
+        // Combining Half Marks
+        | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+/// Normalize `text` for matching.
+///
+/// Decompose `text` into compatibility form (NFKD), strip diacritics of Latin, Greek and Cyrillic
+/// letters, fold case, and compose the result again (NFC), so that e.g. `Müller`, `MULLER` and
+/// `muller` all normalize to the same text, whereas `が` and `か` remain different.
+///
+/// Case folding lowercases all characters, and additionally folds `ß` into `ss`.
+pub fn normalize(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+    for c in text.nfkd().filter(|c| !is_diacritic(*c)) {
+        for lower in c.to_lowercase() {
+            if lower == 'ß' {
+                folded.push_str("ss");
+            } else {
+                folded.push(lower);
+            }
+        }
+    }
+    folded.nfc().collect()
+}
+
+/// Find the character position of the right-most occurrence of `needle` in `haystack`.
+///
+/// Unlike [`str::rfind`] return the position in characters, not in bytes.
+pub fn rfind_chars(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .rfind(needle)
+        .map(|index| haystack[..index].chars().count())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn normalize_ascii() {
+        assert_eq!(normalize("MdCat"), "mdcat");
+    }
+
+    #[test]
+    fn normalize_german() {
+        assert_eq!(normalize("Müller"), "muller");
+        assert_eq!(normalize("MÜLLER"), "muller");
+        assert_eq!(normalize("Straße"), "strasse");
+        assert_eq!(normalize("STRAẞE"), "strasse");
+    }
+
+    #[test]
+    fn normalize_decomposed() {
+        // u followed by a combining diaeresis
+        assert_eq!(normalize("Mu\u{308}ller"), "muller");
+    }
+
+    #[test]
+    fn normalize_compatibility_characters() {
+        assert_eq!(normalize("ﬁle"), "file");
+        // Halfwidth katakana
+        assert_eq!(normalize("ｶﾀｶﾅ"), normalize("カタカナ"));
+    }
+
+    #[test]
+    fn normalize_japanese_and_emoji() {
+        // Precomposed and decomposed voiced marks
+        assert_eq!(
+            normalize("プロジェクト"),
+            normalize("フ\u{309a}ロシ\u{3099}ェクト")
+        );
+        assert_eq!(normalize("日本語"), "日本語");
+        assert_eq!(normalize("🦀 Rust"), "🦀 rust");
+    }
+
+    #[test]
+    fn keep_voiced_marks_of_kana() {
+        assert_ne!(normalize("が"), normalize("か"));
+        assert_ne!(normalize("パ"), normalize("ハ"));
+        assert_eq!(normalize("が"), "が");
+        assert_eq!(normalize("か\u{3099}"), "が");
+        // Halfwidth katakana with a voiced mark
+        assert_eq!(normalize("ｶﾞ"), "ガ");
+    }
+
+    #[test]
+    fn normalize_greek_and_cyrillic() {
+        assert_eq!(normalize("Ἀθῆναι"), "αθηναι");
+        assert_eq!(normalize("Ёлка"), "елка");
+    }
+
+    #[test]
+    fn rfind_in_characters() {
+        assert_eq!(rfind_chars("mdcat", "cat"), Some(2));
+        assert_eq!(rfind_chars("日本語/mdcat", "cat"), Some(6));
+        assert_eq!(rfind_chars("🦀/🦀", "🦀"), Some(2));
+        assert_eq!(rfind_chars("mdcat", "dog"), None);
+    }
+}
@@ -0,0 +1,141 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Scoring of terms matching paths.
+//!
+//! All scorers expect normalized paths and terms, see [`super::normalize`].
+
+use super::rfind_chars;
+
+/// The score of a term which is a whole path component.
+const EXACT_COMPONENT_SCORE: f64 = 1.0;
+
+/// The score of a term which is a prefix of a path component.
+const PREFIX_COMPONENT_SCORE: f64 = 0.75;
+
+/// The score of a term somewhere inside a path component.
+const INNER_COMPONENT_SCORE: f64 = 0.5;
+
+/// The weight of matches in components other than the final one.
+///
+/// Further scaled by the position of the component in the path.
+const PARENT_COMPONENT_WEIGHT: f64 = 0.5;
+
+/// Score `term` by the relative position of its right-most match in `path`.
+///
+/// Offset positions by one so that a match at the very beginning of `path` still counts.  Count
+/// positions in characters, so that non-ASCII characters in `path` don't distort the score.
+///
+/// Return `None` if `term` doesn't occur in `path`, otherwise a score between 0 and 1.
+pub fn position_score(path: &str, term: &str) -> Option<f64> {
+    rfind_chars(path, term)
+        .map(|index| 1.0 * ((index + 1) as f64 / (path.chars().count() + 1) as f64))
+}
+
+/// Score `term` by the path component of `path` it matches best.
+///
+/// A term which is a whole component scores more than a term which is a prefix of a component,
+/// which in turn scores more than a term somewhere inside a component.  Matches in the final
+/// component count fully, matches in parent components count at most half, and less the further
+/// left the component is.  The best match of all components determines the score.
+///
+/// Terms which span multiple components, i.e. contain `/`, can't match single components; these
+/// get scored by [`position_score`].
+///
+/// Return `None` if `term` doesn't occur in `path`, otherwise a score between 0 and 1.
+pub fn component_score(path: &str, term: &str) -> Option<f64> {
+    if term.is_empty() || term.contains('/') {
+        return position_score(path, term);
+    }
+    let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+    let last = components.len().saturating_sub(1);
+    components
+        .iter()
+        .enumerate()
+        .filter_map(|(index, component)| {
+            let score = if *component == term {
+                EXACT_COMPONENT_SCORE
+            } else if component.starts_with(term) {
+                PREFIX_COMPONENT_SCORE
+            } else if component.contains(term) {
+                INNER_COMPONENT_SCORE
+            } else {
+                return None;
+            };
+            let weight = if index == last {
+                1.0
+            } else {
+                PARENT_COMPONENT_WEIGHT * ((index + 1) as f64 / components.len() as f64)
+            };
+            Some(score * weight)
+        })
+        .fold(None, |best: Option<f64>, score| {
+            Some(best.map_or(score, |best| best.max(score)))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn position_score_prefers_matches_further_right() {
+        let left = position_score("foo/dev/bar", "foo").unwrap();
+        let right = position_score("bar/dev/foo", "foo").unwrap();
+        assert!(left < right);
+        assert_eq!(position_score("bar/dev/foo", "baz"), None);
+    }
+
+    #[test]
+    fn exact_component_scores_highest() {
+        let exact = component_score("dev/mdcat", "mdcat").unwrap();
+        let prefix = component_score("dev/mdcat-old", "mdcat").unwrap();
+        let inner = component_score("dev/old-mdcat", "mdcat").unwrap();
+        assert_eq!(exact, 1.0);
+        assert!(prefix < exact);
+        assert!(inner < prefix);
+    }
+
+    #[test]
+    fn final_component_scores_highest() {
+        let parent = component_score("dev/mdcat/docs", "mdcat").unwrap();
+        let last = component_score("dev/docs/mdcat", "mdcat").unwrap();
+        assert!(parent < last);
+        // A weaker match in the final component still beats an exact parent component
+        let inner_last = component_score("mdcat/old-mdcat-fork", "mdcat").unwrap();
+        let exact_parent = component_score("mdcat/fork", "mdcat").unwrap();
+        assert!(exact_parent < inner_last);
+    }
+
+    #[test]
+    fn long_parent_does_not_outrank_exact_final_component() {
+        let long_parent =
+            component_score("projects-with-a-very-long-name-containing-api/web", "api").unwrap();
+        let exact_last = component_score("dev/api", "api").unwrap();
+        assert!(long_parent < exact_last);
+        // The position scorer gets this wrong
+        let long_parent =
+            position_score("projects-with-a-very-long-name-containing-api/web", "api").unwrap();
+        let exact_last = position_score("dev/api", "api").unwrap();
+        assert!(exact_last < long_parent);
+    }
+
+    #[test]
+    fn terms_spanning_components() {
+        assert_eq!(
+            component_score("work/foo", "work/"),
+            position_score("work/foo", "work/")
+        );
+        assert_eq!(component_score("work/foo", "archive/"), None);
+    }
+
+    #[test]
+    fn no_match() {
+        assert_eq!(component_score("dev/mdcat", "pandoc"), None);
+    }
+}
@@ -0,0 +1,236 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Search queries with qualified and negated terms.
+
+use super::normalize;
+
+/// A field of an item which a query term can be restricted to.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum QueryField {
+    /// The name of an item, written as `name:`.
+    Name,
+    /// The path or URI of an item, written as `path:`.
+    Path,
+    /// The app an item belongs to, written as `ide:` or `app:`.
+    App,
+}
+
+impl QueryField {
+    /// Get the field for the given `qualifier`, if any.
+    fn from_qualifier(qualifier: &str) -> Option<Self> {
+        match qualifier {
+            "name" => Some(QueryField::Name),
+            "path" => Some(QueryField::Path),
+            "ide" | "app" => Some(QueryField::App),
+            _ => None,
+        }
+    }
+}
+
+/// A single term of a query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryTerm {
+    /// The field this term is restricted to, or `None` to match any field.
+    pub field: Option<QueryField>,
+    /// The text to match, normalized with [`normalize`].
+    pub text: String,
+    /// Whether items matching this term get excluded.
+    pub negated: bool,
+}
+
+impl QueryTerm {
+    /// Parse a single `term`.
+    ///
+    /// A leading `-` negates the term, and a known qualifier like `name:` restricts the term to
+    /// a field.  Terms with unknown qualifiers remain plain terms.
+    ///
+    /// Return `None` for incomplete terms without any text after the negation or a known
+    /// qualifier, e.g. `-` or `name:`, which Gnome Shell sends while the user is still typing.
+    pub fn parse(term: &str) -> Option<Self> {
+        let (negated, rest) = match term.strip_prefix('-') {
+            Some("") => return None,
+            Some(rest) => (true, rest),
+            None => (false, term),
+        };
+        let (field, text) = match rest.split_once(':') {
+            Some((qualifier, text)) => {
+                match QueryField::from_qualifier(&qualifier.to_lowercase()) {
+                    Some(_) if text.is_empty() => return None,
+                    Some(field) => (Some(field), text),
+                    None => (None, rest),
+                }
+            }
+            None => (None, rest),
+        };
+        Some(Self {
+            field,
+            text: normalize(text),
+            negated,
+        })
+    }
+
+    /// Whether this is a positive term without qualifier.
+    pub fn is_plain(&self) -> bool {
+        self.field.is_none() && !self.negated
+    }
+}
+
+/// A search query, parsed from the terms Gnome Shell sends.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Query {
+    /// The terms of this query.
+    pub terms: Vec<QueryTerm>,
+}
+
+impl Query {
+    /// Parse a query from search `terms`.
+    ///
+    /// Skip incomplete terms, see [`QueryTerm::parse`].
+    pub fn parse<S: AsRef<str>>(terms: &[S]) -> Self {
+        Self {
+            terms: terms
+                .iter()
+                .filter_map(|term| QueryTerm::parse(term.as_ref()))
+                .collect(),
+        }
+    }
+
+    /// Whether this query has qualified or negated terms.
+    ///
+    /// Such terms don't refine the results of the terms typed before them: `name:foo` starts out
+    /// as the plain term `name`, and `-archive` as the incomplete term `-`.
+    pub fn has_filters(&self) -> bool {
+        !self.terms.iter().all(QueryTerm::is_plain)
+    }
+
+    /// All positive terms without qualifiers.
+    pub fn plain_terms(&self) -> impl Iterator<Item = &str> {
+        self.terms
+            .iter()
+            .filter(|term| term.is_plain())
+            .map(|term| term.text.as_str())
+    }
+
+    /// All positive terms restricted to `field`.
+    pub fn field_terms(&self, field: QueryField) -> impl Iterator<Item = &str> {
+        self.terms
+            .iter()
+            .filter(move |term| !term.negated && term.field == Some(field))
+            .map(|term| term.text.as_str())
+    }
+
+    /// All negated terms.
+    pub fn negated_terms(&self) -> impl Iterator<Item = &QueryTerm> {
+        self.terms.iter().filter(|term| term.negated)
+    }
+
+    /// Whether this query admits items of the app with the given `app_id`.
+    ///
+    /// The app ID must contain all positive `ide:` terms and none of the negated `ide:` terms.
+    pub fn matches_app(&self, app_id: &str) -> bool {
+        let app_id = normalize(app_id);
+        self.terms
+            .iter()
+            .filter(|term| term.field == Some(QueryField::App))
+            .all(|term| app_id.contains(&term.text) != term.negated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn term(field: Option<QueryField>, text: &str, negated: bool) -> QueryTerm {
+        QueryTerm {
+            field,
+            text: text.to_string(),
+            negated,
+        }
+    }
+
+    #[test]
+    fn parse_plain_terms() {
+        assert_eq!(QueryTerm::parse("Foo"), Some(term(None, "foo", false)));
+        assert_eq!(
+            QueryTerm::parse("Müller"),
+            Some(term(None, "muller", false))
+        );
+    }
+
+    #[test]
+    fn parse_qualified_terms() {
+        assert_eq!(
+            QueryTerm::parse("name:Foo"),
+            Some(term(Some(QueryField::Name), "foo", false))
+        );
+        assert_eq!(
+            QueryTerm::parse("path:work/"),
+            Some(term(Some(QueryField::Path), "work/", false))
+        );
+        assert_eq!(
+            QueryTerm::parse("IDE:rider"),
+            Some(term(Some(QueryField::App), "rider", false))
+        );
+    }
+
+    #[test]
+    fn parse_negated_terms() {
+        assert_eq!(
+            QueryTerm::parse("-archive"),
+            Some(term(None, "archive", true))
+        );
+        assert_eq!(
+            QueryTerm::parse("-path:old"),
+            Some(term(Some(QueryField::Path), "old", true))
+        );
+    }
+
+    #[test]
+    fn unknown_qualifiers_are_plain_terms() {
+        assert_eq!(
+            QueryTerm::parse("foo:bar"),
+            Some(term(None, "foo:bar", false))
+        );
+        assert_eq!(QueryTerm::parse("foo:"), Some(term(None, "foo:", false)));
+        assert_eq!(
+            QueryTerm::parse("-foo:bar"),
+            Some(term(None, "foo:bar", true))
+        );
+    }
+
+    #[test]
+    fn skip_incomplete_terms() {
+        assert_eq!(QueryTerm::parse("-"), None);
+        assert_eq!(QueryTerm::parse("name:"), None);
+        assert_eq!(QueryTerm::parse("-path:"), None);
+        assert_eq!(
+            Query::parse(&["foo", "-", "name:"]),
+            Query {
+                terms: vec![term(None, "foo", false)]
+            }
+        );
+    }
+
+    #[test]
+    fn filters() {
+        assert!(!Query::parse(&["foo", "-", "name:"]).has_filters());
+        assert!(Query::parse(&["foo", "-a"]).has_filters());
+        assert!(Query::parse(&["name:f"]).has_filters());
+    }
+
+    #[test]
+    fn matches_app() {
+        let app = "jetbrains-rider.desktop";
+        assert!(Query::parse(&["foo"]).matches_app(app));
+        assert!(Query::parse(&["ide:rider", "foo"]).matches_app(app));
+        assert!(!Query::parse(&["ide:idea", "foo"]).matches_app(app));
+        assert!(!Query::parse(&["-ide:rider", "foo"]).matches_app(app));
+        assert!(Query::parse(&["-ide:idea", "foo"]).matches_app(app));
+    }
+}
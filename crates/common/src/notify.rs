@@ -0,0 +1,73 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Notify systemd about the state of the service.
+//!
+//! See `sd_notify(3)`.  All notifications do nothing if systemd didn't start the service, e.g.
+//! if D-Bus started it directly.
+
+use libsystemd::daemon::{notify, watchdog_enabled, NotifyState};
+use tracing::{debug, trace, warn};
+
+/// Send `states` to systemd, and log failures.
+fn send(states: &[NotifyState]) {
+    match notify(false, states) {
+        Ok(true) => trace!("Notified systemd about {:?}", states),
+        Ok(false) => trace!("Not started by systemd, not sending {:?}", states),
+        Err(error) => warn!("Failed to notify systemd about {:?}: {}", states, error),
+    }
+}
+
+/// Tell systemd that the service started, with the given `status`.
+pub fn notify_ready(status: &str) {
+    send(&[NotifyState::Ready, NotifyState::Status(status.to_string())]);
+}
+
+/// Update the status of the service.
+///
+/// Systemd shows the status in `systemctl status`.
+pub fn notify_status(status: &str) {
+    send(&[NotifyState::Status(status.to_string())]);
+}
+
+/// Tell systemd that the service reloads its configuration.
+///
+/// Call [`notify_ready`] once reloading completed.
+pub fn notify_reloading() {
+    send(&[NotifyState::Reloading]);
+}
+
+/// Tell systemd that the service is shutting down.
+pub fn notify_stopping() {
+    send(&[
+        NotifyState::Stopping,
+        NotifyState::Status("Shutting down".to_string()),
+    ]);
+}
+
+/// Keep the systemd watchdog happy, if the service has a watchdog.
+///
+/// Send `WATCHDOG=1` at half the watchdog interval, forever, and return right away if the service
+/// has no watchdog.  Uses Glib timeouts, so the returned future must run on a Glib main context;
+/// if that main context hangs, the service misses the watchdog and systemd restarts it.
+pub async fn keep_watchdog_alive() {
+    let timeout = match watchdog_enabled(false) {
+        Some(timeout) => timeout,
+        None => {
+            debug!("No watchdog enabled");
+            return;
+        }
+    };
+    let interval = timeout / 2;
+    debug!(
+        "Watchdog timeout {:?}, notifying every {:?}",
+        timeout, interval
+    );
+    loop {
+        send(&[NotifyState::Watchdog]);
+        glib::timeout_future(interval).await;
+    }
+}
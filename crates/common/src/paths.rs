@@ -0,0 +1,145 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Utilities for paths of items.
+
+use std::path::PathBuf;
+
+/// The home directory of the current user, as string.
+pub fn home_dir() -> String {
+    glib::home_dir().to_string_lossy().to_string()
+}
+
+/// The directory for state data of the current user.
+///
+/// This is `$XDG_STATE_HOME`, or `~/.local/state` if `$XDG_STATE_HOME` is unset or not absolute.
+pub fn user_state_dir() -> PathBuf {
+    std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .unwrap_or_else(|| glib::home_dir().join(".local").join("state"))
+}
+
+/// Expand a leading `~` in `path` to the home directory of the current user.
+pub fn expand_home(path: &str) -> String {
+    if path == "~" {
+        home_dir()
+    } else if let Some(rest) = path.strip_prefix("~/") {
+        format!("{}/{}", home_dir().trim_end_matches('/'), rest)
+    } else {
+        path.to_string()
+    }
+}
+
+/// Strip `root` from the beginning of `path`.
+///
+/// Only strip `root` if it ends at a path separator in `path`.  Return the remainder of `path`
+/// without the leading separator, or `None` if `path` is not inside `root`.
+fn strip_root<'a>(path: &'a str, root: &str) -> Option<&'a str> {
+    let root = root.trim_end_matches('/');
+    if root.is_empty() {
+        return None;
+    }
+    match path.strip_prefix(root)? {
+        "" => Some(""),
+        rest => rest.strip_prefix('/'),
+    }
+}
+
+/// Strip the longest of `roots` from the beginning of `path`.
+///
+/// Return `path` unchanged if it isn't inside any of `roots`, and the last component of `path`
+/// if `path` is one of `roots`.
+pub fn strip_roots<'a, S: AsRef<str>>(path: &'a str, roots: &[S]) -> &'a str {
+    match roots
+        .iter()
+        .filter_map(|root| strip_root(path, root.as_ref()))
+        .min_by_key(|rest| rest.len())
+    {
+        Some("") => path
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or(path),
+        Some(rest) => rest,
+        None => path,
+    }
+}
+
+/// Make `path` relative to `home`, for display.
+///
+/// Replace `home` at the beginning of `path` with `~`.
+pub fn display_path_relative_to(path: &str, home: &str) -> String {
+    match strip_root(path, home) {
+        Some("") => "~".to_string(),
+        Some(rest) => format!("~/{}", rest),
+        None => path.to_string(),
+    }
+}
+
+/// Make `path` relative to the home directory of the current user, for display.
+pub fn display_path(path: &str) -> String {
+    display_path_relative_to(path, &home_dir())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn strip_longest_root() {
+        let roots = ["/home/foo", "/home/foo/Code/gh"];
+        assert_eq!(strip_roots("/home/foo/Code/gh/mdcat", &roots), "mdcat");
+        assert_eq!(
+            strip_roots("/home/foo/Code/gitlab/mdcat", &roots),
+            "Code/gitlab/mdcat"
+        );
+        assert_eq!(strip_roots("/srv/mdcat", &roots), "/srv/mdcat");
+    }
+
+    #[test]
+    fn keep_last_component_of_root() {
+        let roots = ["/home/foo", "/home/foo/Code/gh"];
+        assert_eq!(strip_roots("/home/foo", &roots), "foo");
+        assert_eq!(strip_roots("/home/foo/Code/gh/", &roots), "gh");
+    }
+
+    #[test]
+    fn strip_roots_at_separators_only() {
+        assert_eq!(
+            strip_roots("/home/foobar/mdcat", &["/home/foo"]),
+            "/home/foobar/mdcat"
+        );
+        assert_eq!(strip_roots("/home/foo/mdcat", &["/home/foo/"]), "mdcat");
+    }
+
+    #[test]
+    fn display_relative_to_home() {
+        assert_eq!(
+            display_path_relative_to("/home/foo/Code/mdcat", "/home/foo"),
+            "~/Code/mdcat"
+        );
+        assert_eq!(display_path_relative_to("/home/foo", "/home/foo"), "~");
+        assert_eq!(
+            display_path_relative_to("/home/foobar/mdcat", "/home/foo"),
+            "/home/foobar/mdcat"
+        );
+    }
+
+    #[test]
+    fn expand_home_dir() {
+        let home = home_dir();
+        assert_eq!(expand_home("~"), home);
+        assert_eq!(
+            expand_home("~/Code/gh"),
+            format!("{}/Code/gh", home.trim_end_matches('/'))
+        );
+        assert_eq!(expand_home("/srv/code"), "/srv/code");
+        assert_eq!(expand_home("~foo/code"), "~foo/code");
+    }
+}
@@ -5,17 +5,30 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 //! Sources for matchable items.
+//!
+//! Besides the [`AsyncItemsSource`] trait this module provides combinators to assemble sources
+//! from parts, see [`AsyncItemsSourceExt`].
 
-use std::fmt::Display;
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
+use futures_util::future::{join, select, Either};
+use futures_util::stream::{self, BoxStream, StreamExt};
 use indexmap::map::IndexMap;
+use parking_lot::Mutex;
+use tracing::{debug, trace};
 
 use crate::matching::ScoreMatchable;
 
 /// A map of IDs to items which can be matched.
 pub type IdMap<I> = IndexMap<String, I>;
 
+/// A stream of notifications about changed items of a source.
+pub type ItemsChanges = BoxStream<'static, ()>;
+
 /// A async source for matchable items.
 #[async_trait]
 pub trait AsyncItemsSource<T: ScoreMatchable> {
@@ -24,4 +37,553 @@ pub trait AsyncItemsSource<T: ScoreMatchable> {
 
     /// Find matchable items.
     async fn find_recent_items(&self) -> Result<IdMap<T>, Self::Err>;
+
+    /// Watch this source for changes of its items.
+    ///
+    /// Return a stream which yields whenever the items of this source change, e.g. because a file
+    /// monitor reported a change of an underlying file.  Users then only need to find items again
+    /// after a change, instead of before every use.
+    ///
+    /// Return `None` if this source can't tell about changes; this is the default.
+    fn watch(&self) -> Option<ItemsChanges> {
+        None
+    }
+}
+
+/// Combinators for [`AsyncItemsSource`].
+pub trait AsyncItemsSourceExt<T: ScoreMatchable>: AsyncItemsSource<T> + Sized {
+    /// Cache items of this source for `max_age`.
+    ///
+    /// Return cached items until they are older than `max_age`, and only then ask this source
+    /// again.  Errors are not cached.  If this source reports changes, forget cached items on
+    /// every change.
+    fn cached(self, max_age: Duration) -> Cached<Self, T> {
+        Cached {
+            source: self,
+            max_age,
+            cache: Arc::new(Mutex::new(Cache::default())),
+        }
+    }
+
+    /// Merge the items of this source and `other`.
+    ///
+    /// If both sources have an item with the same ID, use the item of this source.  Fail if
+    /// either source fails.
+    fn merged<O: AsyncItemsSource<T>>(self, other: O) -> Merged<Self, O> {
+        Merged {
+            first: self,
+            second: other,
+        }
+    }
+
+    /// Only return items of this source which satisfy `predicate`.
+    ///
+    /// `predicate` receives the ID and the item.
+    fn filtered<F>(self, predicate: F) -> Filtered<Self, F>
+    where
+        F: Fn(&str, &T) -> bool,
+    {
+        Filtered {
+            source: self,
+            predicate,
+        }
+    }
+
+    /// Convert all items of this source with `f`.
+    fn mapped<U, F>(self, f: F) -> Mapped<Self, F, T>
+    where
+        U: ScoreMatchable,
+        F: Fn(T) -> U,
+    {
+        Mapped {
+            source: self,
+            f,
+            _item: PhantomData,
+        }
+    }
+
+    /// Fail if this source takes longer than `timeout` to return items.
+    ///
+    /// Uses a Glib timeout, so the source must run on a Glib main context.
+    fn with_timeout(self, timeout: Duration) -> WithTimeout<Self> {
+        WithTimeout {
+            source: self,
+            timeout,
+        }
+    }
+}
+
+impl<T: ScoreMatchable, S: AsyncItemsSource<T>> AsyncItemsSourceExt<T> for S {}
+
+/// Cached items of a source.
+#[derive(Debug)]
+struct Cache<T> {
+    /// Incremented whenever the cache is invalidated.
+    ///
+    /// Loads which started at an older generation must not store their items, because the
+    /// items may have changed while loading.
+    generation: u64,
+    /// The cached items, and when they were loaded.
+    items: Option<(Instant, IdMap<T>)>,
+}
+
+impl<T> Default for Cache<T> {
+    fn default() -> Self {
+        Self {
+            generation: 0,
+            items: None,
+        }
+    }
+}
+
+impl<T> Cache<T> {
+    fn invalidate(&mut self) {
+        self.generation += 1;
+        self.items = None;
+    }
+}
+
+/// A source which caches items of another source.
+///
+/// See [`AsyncItemsSourceExt::cached`].
+#[derive(Debug)]
+pub struct Cached<S, T> {
+    source: S,
+    max_age: Duration,
+    cache: Arc<Mutex<Cache<T>>>,
+}
+
+impl<S, T> Cached<S, T> {
+    /// Forget the cached items, and ask the underlying source on the next call.
+    ///
+    /// Items of loads in progress are not cached.
+    pub fn invalidate(&self) {
+        self.cache.lock().invalidate();
+    }
+}
+
+#[async_trait]
+impl<S, T> AsyncItemsSource<T> for Cached<S, T>
+where
+    S: AsyncItemsSource<T> + Send + Sync,
+    T: ScoreMatchable + Clone + Send + Sync,
+{
+    type Err = S::Err;
+
+    async fn find_recent_items(&self) -> Result<IdMap<T>, Self::Err> {
+        let generation = {
+            let cache = self.cache.lock();
+            if let Some((loaded, items)) = cache.items.as_ref() {
+                if loaded.elapsed() < self.max_age {
+                    trace!("Returning {} cached item(s)", items.len());
+                    return Ok(items.clone());
+                }
+            }
+            cache.generation
+        };
+        let items = self.source.find_recent_items().await?;
+        let mut cache = self.cache.lock();
+        if cache.generation == generation {
+            debug!("Caching {} item(s) for {:?}", items.len(), self.max_age);
+            cache.items = Some((Instant::now(), items.clone()));
+        } else {
+            debug!("Cache invalidated while loading items, not caching stale items");
+        }
+        Ok(items)
+    }
+
+    fn watch(&self) -> Option<ItemsChanges> {
+        let cache = self.cache.clone();
+        self.source.watch().map(|changes| {
+            changes
+                .map(move |()| {
+                    trace!("Items changed, invalidating cache");
+                    cache.lock().invalidate();
+                })
+                .boxed()
+        })
+    }
+}
+
+/// An error from one of two merged sources.
+#[derive(Debug)]
+pub enum MergedError<E1, E2> {
+    /// The first source failed.
+    First(E1),
+    /// The second source failed.
+    Second(E2),
+}
+
+impl<E1: Display, E2: Display> Display for MergedError<E1, E2> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergedError::First(error) => error.fmt(f),
+            MergedError::Second(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E1, E2> std::error::Error for MergedError<E1, E2>
+where
+    E1: std::error::Error,
+    E2: std::error::Error,
+{
+}
+
+/// A source which merges the items of two sources.
+///
+/// See [`AsyncItemsSourceExt::merged`].
+#[derive(Debug)]
+pub struct Merged<S1, S2> {
+    first: S1,
+    second: S2,
+}
+
+#[async_trait]
+impl<S1, S2, T> AsyncItemsSource<T> for Merged<S1, S2>
+where
+    S1: AsyncItemsSource<T> + Send + Sync,
+    S2: AsyncItemsSource<T> + Send + Sync,
+    S1::Err: Send,
+    S2::Err: Send,
+    T: ScoreMatchable + Send,
+{
+    type Err = MergedError<S1::Err, S2::Err>;
+
+    async fn find_recent_items(&self) -> Result<IdMap<T>, Self::Err> {
+        let (first, second) = join(
+            self.first.find_recent_items(),
+            self.second.find_recent_items(),
+        )
+        .await;
+        let mut items = first.map_err(MergedError::First)?;
+        for (id, item) in second.map_err(MergedError::Second)? {
+            if items.contains_key(&id) {
+                trace!("Skipping duplicate item {}", id);
+            } else {
+                items.insert(id, item);
+            }
+        }
+        Ok(items)
+    }
+
+    fn watch(&self) -> Option<ItemsChanges> {
+        match (self.first.watch(), self.second.watch()) {
+            (Some(first), Some(second)) => Some(stream::select(first, second).boxed()),
+            (Some(changes), None) | (None, Some(changes)) => Some(changes),
+            (None, None) => None,
+        }
+    }
+}
+
+/// A source which filters the items of another source.
+///
+/// See [`AsyncItemsSourceExt::filtered`].
+#[derive(Debug)]
+pub struct Filtered<S, F> {
+    source: S,
+    predicate: F,
+}
+
+#[async_trait]
+impl<S, F, T> AsyncItemsSource<T> for Filtered<S, F>
+where
+    S: AsyncItemsSource<T> + Send + Sync,
+    F: Fn(&str, &T) -> bool + Send + Sync,
+    T: ScoreMatchable + Send,
+{
+    type Err = S::Err;
+
+    async fn find_recent_items(&self) -> Result<IdMap<T>, Self::Err> {
+        let mut items = self.source.find_recent_items().await?;
+        items.retain(|id, item| (self.predicate)(id.as_str(), &*item));
+        Ok(items)
+    }
+
+    fn watch(&self) -> Option<ItemsChanges> {
+        self.source.watch()
+    }
+}
+
+/// A source which converts the items of another source.
+///
+/// See [`AsyncItemsSourceExt::mapped`].
+#[derive(Debug)]
+pub struct Mapped<S, F, T> {
+    source: S,
+    f: F,
+    _item: PhantomData<fn() -> T>,
+}
+
+#[async_trait]
+impl<S, F, T, U> AsyncItemsSource<U> for Mapped<S, F, T>
+where
+    S: AsyncItemsSource<T> + Send + Sync,
+    F: Fn(T) -> U + Send + Sync,
+    T: ScoreMatchable + Send,
+    U: ScoreMatchable + Send,
+{
+    type Err = S::Err;
+
+    async fn find_recent_items(&self) -> Result<IdMap<U>, Self::Err> {
+        let items = self.source.find_recent_items().await?;
+        Ok(items
+            .into_iter()
+            .map(|(id, item)| (id, (self.f)(item)))
+            .collect())
+    }
+
+    fn watch(&self) -> Option<ItemsChanges> {
+        self.source.watch()
+    }
+}
+
+/// An error of a source with a timeout.
+#[derive(Debug)]
+pub enum TimeoutError<E> {
+    /// The source took longer than the given timeout.
+    Elapsed(Duration),
+    /// The source failed.
+    Source(E),
+}
+
+impl<E: Display> Display for TimeoutError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeoutError::Elapsed(timeout) => {
+                write!(f, "Loading items took longer than {:?}", timeout)
+            }
+            TimeoutError::Source(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error> std::error::Error for TimeoutError<E> {}
+
+/// A source which fails if another source takes too long.
+///
+/// See [`AsyncItemsSourceExt::with_timeout`].
+#[derive(Debug)]
+pub struct WithTimeout<S> {
+    source: S,
+    timeout: Duration,
+}
+
+#[async_trait]
+impl<S, T> AsyncItemsSource<T> for WithTimeout<S>
+where
+    S: AsyncItemsSource<T> + Send + Sync,
+    T: ScoreMatchable + Send,
+{
+    type Err = TimeoutError<S::Err>;
+
+    async fn find_recent_items(&self) -> Result<IdMap<T>, Self::Err> {
+        match select(
+            self.source.find_recent_items(),
+            glib::timeout_future(self.timeout),
+        )
+        .await
+        {
+            Either::Left((result, _)) => result.map_err(TimeoutError::Source),
+            Either::Right(_) => Err(TimeoutError::Elapsed(self.timeout)),
+        }
+    }
+
+    fn watch(&self) -> Option<ItemsChanges> {
+        self.source.watch()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use pretty_assertions::assert_eq;
+
+    use crate::app::AppLaunchItem;
+    use crate::testutil::block_on;
+
+    use super::*;
+
+    fn item(name: &str) -> AppLaunchItem {
+        AppLaunchItem::new(name.to_string(), format!("/srv/{}", name), Vec::new())
+    }
+
+    /// A source of fixed items, which counts how often it was asked for items.
+    #[derive(Default)]
+    struct StaticSource {
+        items: Vec<(&'static str, &'static str)>,
+        delay: Option<Duration>,
+        calls: AtomicUsize,
+        changes: Mutex<Option<ItemsChanges>>,
+    }
+
+    impl StaticSource {
+        fn new(items: &[(&'static str, &'static str)]) -> Self {
+            Self {
+                items: items.to_vec(),
+                ..StaticSource::default()
+            }
+        }
+    }
+
+    #[async_trait]
+    impl AsyncItemsSource<AppLaunchItem> for StaticSource {
+        type Err = String;
+
+        async fn find_recent_items(&self) -> Result<IdMap<AppLaunchItem>, Self::Err> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if let Some(delay) = self.delay {
+                glib::timeout_future(delay).await;
+            }
+            Ok(self
+                .items
+                .iter()
+                .map(|(id, name)| (id.to_string(), item(name)))
+                .collect())
+        }
+
+        fn watch(&self) -> Option<ItemsChanges> {
+            self.changes.lock().take()
+        }
+    }
+
+    /// A source which always fails.
+    struct FailingSource;
+
+    #[async_trait]
+    impl AsyncItemsSource<AppLaunchItem> for FailingSource {
+        type Err = String;
+
+        async fn find_recent_items(&self) -> Result<IdMap<AppLaunchItem>, Self::Err> {
+            Err("failed".to_string())
+        }
+    }
+
+    fn names(items: &IdMap<AppLaunchItem>) -> Vec<(&str, &str)> {
+        items
+            .iter()
+            .map(|(id, item)| (id.as_str(), item.name()))
+            .collect()
+    }
+
+    #[test]
+    fn cached() {
+        let source = StaticSource::new(&[("1", "foo")]).cached(Duration::from_secs(60));
+        let items = block_on(source.find_recent_items()).unwrap();
+        assert_eq!(names(&items), [("1", "foo")]);
+        block_on(source.find_recent_items()).unwrap();
+        assert_eq!(source.source.calls.load(Ordering::SeqCst), 1);
+
+        source.invalidate();
+        block_on(source.find_recent_items()).unwrap();
+        assert_eq!(source.source.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn cached_expires() {
+        let source = StaticSource::new(&[("1", "foo")]).cached(Duration::ZERO);
+        block_on(source.find_recent_items()).unwrap();
+        block_on(source.find_recent_items()).unwrap();
+        assert_eq!(source.source.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn cached_discards_items_invalidated_while_loading() {
+        let source = StaticSource {
+            delay: Some(Duration::from_millis(100)),
+            ..StaticSource::new(&[("1", "foo")])
+        }
+        .cached(Duration::from_secs(60));
+        let (items, _) = block_on(join(source.find_recent_items(), async {
+            glib::timeout_future(Duration::from_millis(10)).await;
+            source.invalidate();
+        }));
+        assert_eq!(names(&items.unwrap()), [("1", "foo")]);
+        block_on(source.find_recent_items()).unwrap();
+        assert_eq!(source.source.calls.load(Ordering::SeqCst), 2);
+        block_on(source.find_recent_items()).unwrap();
+        assert_eq!(source.source.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn cached_invalidates_on_changes() {
+        let source = StaticSource {
+            changes: Mutex::new(Some(stream::iter(vec![()]).boxed())),
+            ..StaticSource::new(&[("1", "foo")])
+        }
+        .cached(Duration::from_secs(60));
+        block_on(source.find_recent_items()).unwrap();
+        let changes = source.watch().unwrap();
+        assert_eq!(block_on(changes.count()), 1);
+        block_on(source.find_recent_items()).unwrap();
+        assert_eq!(source.source.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn merged() {
+        let source = StaticSource::new(&[("1", "foo"), ("2", "bar")])
+            .merged(StaticSource::new(&[("2", "other"), ("3", "baz")]));
+        let items = block_on(source.find_recent_items()).unwrap();
+        assert_eq!(names(&items), [("1", "foo"), ("2", "bar"), ("3", "baz")]);
+    }
+
+    #[test]
+    fn merged_fails() {
+        let source = StaticSource::new(&[("1", "foo")]).merged(FailingSource);
+        let error = block_on(source.find_recent_items()).unwrap_err();
+        assert_eq!(error.to_string(), "failed");
+    }
+
+    #[test]
+    fn merged_watches_both_sources() {
+        let watched = |n: usize| StaticSource {
+            changes: Mutex::new(Some(stream::iter(vec![(); n]).boxed())),
+            ..StaticSource::default()
+        };
+        let changes = watched(1).merged(watched(2)).watch().unwrap();
+        assert_eq!(block_on(changes.count()), 3);
+        let changes = watched(2).merged(StaticSource::default()).watch().unwrap();
+        assert_eq!(block_on(changes.count()), 2);
+        assert!(StaticSource::default()
+            .merged(StaticSource::default())
+            .watch()
+            .is_none());
+    }
+
+    #[test]
+    fn filtered() {
+        let source = StaticSource::new(&[("1", "foo"), ("2", "bar")])
+            .filtered(|_, item: &AppLaunchItem| item.name() != "foo");
+        let items = block_on(source.find_recent_items()).unwrap();
+        assert_eq!(names(&items), [("2", "bar")]);
+    }
+
+    #[test]
+    fn mapped() {
+        let source = StaticSource::new(&[("1", "foo")]).mapped(|item: AppLaunchItem| {
+            AppLaunchItem::new(
+                item.name().to_string(),
+                item.uri().to_string(),
+                vec!["static".to_string()],
+            )
+        });
+        let items = block_on(source.find_recent_items()).unwrap();
+        assert_eq!(items["1"].keywords(), ["static"]);
+    }
+
+    #[test]
+    fn with_timeout() {
+        let source = StaticSource {
+            delay: Some(Duration::from_secs(10)),
+            ..StaticSource::new(&[("1", "foo")])
+        }
+        .with_timeout(Duration::from_millis(10));
+        let error = block_on(source.find_recent_items()).unwrap_err();
+        assert!(matches!(error, TimeoutError::Elapsed(_)));
+
+        let source = StaticSource::new(&[("1", "foo")]).with_timeout(Duration::from_secs(10));
+        let items = block_on(source.find_recent_items()).unwrap();
+        assert_eq!(names(&items), [("1", "foo")]);
+    }
 }
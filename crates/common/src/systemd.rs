@@ -6,12 +6,20 @@
 
 //! Systemd utilities.
 
+use std::convert::TryInto;
+
 use libc::pid_t;
 use libsystemd::unit::escape_name;
 use tracing::{debug, trace};
 use zbus::dbus_proxy;
 use zbus::zvariant::{OwnedObjectPath, Value};
 
+/// The bus name of the systemd manager.
+const SYSTEMD1_BUSNAME: &str = "org.freedesktop.systemd1";
+
+/// The error systemd returns when starting a unit which already exists.
+const UNIT_EXISTS_ERROR: &str = "org.freedesktop.systemd1.UnitExists";
+
 /// The systemd manager DBUS API.
 ///
 /// See <https://www.freedesktop.org/wiki/Software/systemd/dbus/>
@@ -36,6 +44,60 @@ pub trait Systemd1Manager {
     ) -> zbus::Result<OwnedObjectPath>;
 }
 
+/// The DBUS API of a systemd unit.
+///
+/// See <https://www.freedesktop.org/wiki/Software/systemd/dbus/>
+#[dbus_proxy(
+    interface = "org.freedesktop.systemd1.Unit",
+    default_service = "org.freedesktop.systemd1"
+)]
+pub trait Systemd1Unit {
+    /// The state of the unit, e.g. `active`, `deactivating` or `failed`.
+    #[dbus_proxy(property)]
+    fn active_state(&self) -> zbus::Result<String>;
+}
+
+/// Resource controls for a systemd scope.
+///
+/// See `systemd.resource-control(5)`.  Unset controls take the defaults of systemd.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ScopeResources {
+    /// The slice to put the scope in, e.g. `app-jetbrains.slice`.
+    ///
+    /// Systemd creates the slice if it doesn't exist yet.
+    pub slice: Option<String>,
+    /// The memory usage in bytes above which systemd throttles processes of the scope.
+    pub memory_high: Option<u64>,
+    /// The memory usage in bytes above which the kernel kills processes of the scope.
+    pub memory_max: Option<u64>,
+    /// The relative weight of the scope for CPU time, between 1 and 10000.
+    pub cpu_weight: Option<u64>,
+    /// The relative weight of the scope for IO, between 1 and 10000.
+    pub io_weight: Option<u64>,
+}
+
+impl ScopeResources {
+    /// The unit properties for these resource controls.
+    fn properties(&self) -> Vec<(&'static str, Value<'_>)> {
+        let mut props = Vec::new();
+        if let Some(slice) = &self.slice {
+            props.push(("Slice", Value::Str(slice.as_str().into())));
+        }
+        let limits = [
+            ("MemoryHigh", self.memory_high),
+            ("MemoryMax", self.memory_max),
+            ("CPUWeight", self.cpu_weight),
+            ("IOWeight", self.io_weight),
+        ];
+        for (name, value) in limits {
+            if let Some(value) = value {
+                props.push((name, Value::U64(value)));
+            }
+        }
+        props
+    }
+}
+
 /// Properties for a new systemd scope.
 #[derive(Debug)]
 pub struct ScopeProperties<'a> {
@@ -58,19 +120,49 @@ pub struct ScopeProperties<'a> {
     pub description: Option<&'a str>,
     /// The optional documentation URLs for the unit.
     pub documentation: Vec<&'a str>,
+    /// Resource controls for the unit.
+    pub resources: &'a ScopeResources,
+}
+
+/// Whether a systemd manager is reachable on `connection`.
+///
+/// This is not the case if the session doesn't run under systemd, or if `connection` doesn't
+/// talk to a bus at all.
+pub async fn systemd_manager_available(connection: &zbus::Connection) -> bool {
+    let result = match zbus::fdo::DBusProxy::new(connection).await {
+        Ok(proxy) => {
+            proxy
+                .name_has_owner(SYSTEMD1_BUSNAME.try_into().unwrap())
+                .await
+        }
+        Err(error) => Err(error.into()),
+    };
+    trace!("NameHasOwner({}) -> {:?}", SYSTEMD1_BUSNAME, result);
+    result.unwrap_or(false)
+}
+
+/// Whether `error` tells that a unit already exists.
+pub fn is_unit_exists_error(error: &zbus::Error) -> bool {
+    match error {
+        zbus::Error::MethodError(name, _, _) => name.as_str() == UNIT_EXISTS_ERROR,
+        _ => false,
+    }
 }
 
 /// Start a new systemd application scope for a running process.
 ///
 /// `properties` provides the name and the metadata for the new scope.
 ///
-/// `pid` is the process ID of the process to move into a new scope.
+/// `pid` is the process ID of the process to move into a new scope.  `attempt` counts attempts
+/// to start a scope for the same process; every attempt after the first appends the attempt to
+/// the scope name, to make the name unique.
 ///
 /// Return the complete name and the DBUS object path of the new scope unit if successful.
 pub async fn start_app_scope(
     manager: &Systemd1ManagerProxy<'_>,
-    properties: ScopeProperties<'_>,
+    properties: &ScopeProperties<'_>,
     pid: pid_t,
+    attempt: u32,
 ) -> zbus::Result<(String, OwnedObjectPath)> {
     // See https://gitlab.gnome.org/jf/start-transient-unit/-/blob/117c6f32c8dc0d1f28686408f698632aa71880bc/rust/src/main.rs#L94
     // for inspiration.
@@ -95,19 +187,30 @@ pub async fn start_app_scope(
     if !properties.documentation.is_empty() {
         props.push((
             "Documentation",
-            Value::Array(properties.documentation.into()),
+            Value::Array(properties.documentation.clone().into()),
         ))
     }
+    props.extend(properties.resources.properties());
     // This is roughly what Gnome itself does when it moves a new process to a systemd scope, see
     // https://gitlab.gnome.org/GNOME/gnome-desktop/-/blob/106a729c3f98b8ee56823a0a49fa8504f78dd355/libgnome-desktop/gnome-systemd.c#L81
     //
     // Gnome Shell uses a the app-gnome prefix; we make the prefix configurable to allow callers to identify their new scopes.
-    let name = format!(
-        "{}-{}-{}.scope",
-        properties.prefix,
-        escape_name(properties.name),
-        pid
-    );
+    let name = if attempt == 0 {
+        format!(
+            "{}-{}-{}.scope",
+            properties.prefix,
+            escape_name(properties.name),
+            pid
+        )
+    } else {
+        format!(
+            "{}-{}-{}-{}.scope",
+            properties.prefix,
+            escape_name(properties.name),
+            pid,
+            attempt
+        )
+    };
     debug!("Creating new scope {} for {}", &name, pid);
 
     // We `fail` to start the scope if it already exists.
@@ -130,3 +233,34 @@ pub async fn start_app_scope(
     );
     result.map(|objpath| (name, objpath))
 }
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn no_resource_properties_by_default() {
+        assert!(ScopeResources::default().properties().is_empty());
+    }
+
+    #[test]
+    fn resource_properties() {
+        let resources = ScopeResources {
+            slice: Some("app-jetbrains.slice".to_string()),
+            memory_high: Some(4 * 1024 * 1024 * 1024),
+            memory_max: None,
+            cpu_weight: Some(50),
+            io_weight: None,
+        };
+        assert_eq!(
+            resources.properties(),
+            vec![
+                ("Slice", Value::Str("app-jetbrains.slice".into())),
+                ("MemoryHigh", Value::U64(4 * 1024 * 1024 * 1024)),
+                ("CPUWeight", Value::U64(50)),
+            ]
+        );
+    }
+}
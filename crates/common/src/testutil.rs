@@ -0,0 +1,64 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Utilities for tests of search providers.
+//!
+//! Only available in tests of this crate, or with the `test-util` feature.
+
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+/// A temporary directory, deleted on drop.
+#[derive(Debug)]
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create a new temporary directory.
+    pub fn new() -> Self {
+        Self(
+            glib::mkdtemp(std::env::temp_dir().join("gnome-search-provider-XXXXXX"))
+                .expect("Failed to create temporary directory"),
+        )
+    }
+
+    /// The path of this directory.
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Create a directory `name` in this directory, with the given `files`.
+    ///
+    /// `files` are pairs of paths relative to the new directory and their contents.  Return the
+    /// path of the new directory.
+    pub fn create_dir(&self, name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let path = self.0.join(name);
+        for (filename, contents) in files {
+            let file = path.join(filename);
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            std::fs::write(file, contents).unwrap();
+        }
+        std::fs::create_dir_all(&path).unwrap();
+        path
+    }
+}
+
+impl Default for TempDir {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Run `future` to completion on a new Glib main context.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let context = glib::MainContext::new();
+    context.with_thread_default(|| context.block_on(future))
+}
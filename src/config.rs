@@ -0,0 +1,687 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Configuration of the service.
+//!
+//! The configuration lives in `$XDG_CONFIG_HOME/gnome-search-providers-jetbrains/config.ini`,
+//! in the format of Glib's key files, i.e.
+//!
+//! ```ini
+//! [search]
+//! load-timeout-ms=2000
+//! root-prefixes=~/Code/gh;~/Code/work
+//! max-results=5
+//! min-score=1.5
+//!
+//! [projects]
+//! name-sources=idea;gradle;maven;cargo;npm;pyproject;directory
+//!
+//! [scope]
+//! mode=retry
+//! slice=app-jetbrains.slice
+//! memory-high=6G
+//! memory-max=8G
+//! cpu-weight=50
+//! io-weight=50
+//!
+//! [launch]
+//! env=_JAVA_OPTIONS=-Dawt.toolkit.name=WLToolkit
+//! unset-env=JAVA_HOME
+//!
+//! [provider:jetbrains-idea.desktop]
+//! max-results=10
+//! memory-max=12G
+//! cli=~/.local/bin/idea
+//! env=IDEA_JDK=/usr/lib/jvm/java-17-openjdk
+//! args=nosplash
+//!
+//! [service]
+//! idle-timeout-s=600
+//! ```
+//!
+//! Groups named `provider:` followed by the desktop ID of an IDE override settings for the search
+//! provider of that IDE: `max-results` and `min-score` from the `[search]` group, the resource
+//! controls from the `[scope]` group, and all settings from the `[launch]` group.  The other
+//! settings of `[search]` and the scope `mode` apply to all providers.  These groups also take
+//! the `cli` launcher of the IDE.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use tracing::{debug, info};
+
+use gnome_search_provider_common::app::{LaunchOptions, ScopeMode, ScopeResources};
+use gnome_search_provider_common::glib;
+use gnome_search_provider_common::paths::expand_home;
+
+use crate::project::{NameSource, DEFAULT_NAME_SOURCES};
+
+/// The group for search settings.
+const SEARCH_GROUP: &str = "search";
+
+/// The group for settings about projects.
+const PROJECTS_GROUP: &str = "projects";
+
+/// The group for resource controls of systemd scopes of launched IDEs.
+const SCOPE_GROUP: &str = "scope";
+
+/// The group for the environment and arguments of launched IDEs.
+const LAUNCH_GROUP: &str = "launch";
+
+/// The group for settings of the service itself.
+const SERVICE_GROUP: &str = "service";
+
+/// The prefix of groups for settings of individual providers.
+const PROVIDER_GROUP_PREFIX: &str = "provider:";
+
+/// Settings which can differ between search providers.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ProviderConfig {
+    /// The maximum number of results of a search, or `None` to return all results.
+    pub max_results: Option<usize>,
+    /// The minimum score of search results.
+    pub min_score: f64,
+    /// Resource controls for systemd scopes of launched IDEs.
+    pub scope: ScopeResources,
+    /// Extra environment variables and arguments for launched IDEs.
+    pub launch: LaunchOptions,
+    /// The name of or the path to the command line launcher of the IDE, to open projects in a
+    /// running instance.
+    ///
+    /// `None` to use the default launcher of the IDE.
+    pub cli: Option<String>,
+}
+
+impl ProviderConfig {
+    /// Read settings from `group` in `keyfile`, on top of `self`.
+    fn merge_key_file(mut self, keyfile: &glib::KeyFile, group: &str) -> Result<Self> {
+        if let Some(max_results) = optional_integer(keyfile, group, "max-results")? {
+            self.max_results = usize::try_from(max_results).ok().filter(|n| 0 < *n);
+        }
+        if let Some(min_score) = optional_double(keyfile, group, "min-score")? {
+            self.min_score = min_score;
+        }
+        Ok(self)
+    }
+
+    /// Read resource controls for scopes from `group` in `keyfile`, on top of `self`.
+    fn merge_scope_key_file(mut self, keyfile: &glib::KeyFile, group: &str) -> Result<Self> {
+        if let Some(slice) = optional_string(keyfile, group, "slice")? {
+            let slice = slice.trim();
+            if !slice.ends_with(".slice") {
+                return Err(anyhow!(
+                    "Invalid value for slice in [{}]: {} is not a slice unit",
+                    group,
+                    slice
+                ));
+            }
+            self.scope.slice = Some(slice.to_string());
+        }
+        if let Some(size) = optional_size(keyfile, group, "memory-high")? {
+            self.scope.memory_high = Some(size);
+        }
+        if let Some(size) = optional_size(keyfile, group, "memory-max")? {
+            self.scope.memory_max = Some(size);
+        }
+        if let Some(weight) = optional_weight(keyfile, group, "cpu-weight")? {
+            self.scope.cpu_weight = Some(weight);
+        }
+        if let Some(weight) = optional_weight(keyfile, group, "io-weight")? {
+            self.scope.io_weight = Some(weight);
+        }
+        Ok(self)
+    }
+
+    /// Read the environment and arguments of launched IDEs from `group` in `keyfile`, on top of
+    /// `self`.
+    fn merge_launch_key_file(mut self, keyfile: &glib::KeyFile, group: &str) -> Result<Self> {
+        if let Some(env) = optional_string_list(keyfile, group, "env")? {
+            self.launch.env = env
+                .iter()
+                .map(|variable| {
+                    parse_variable(variable).ok_or_else(|| {
+                        anyhow!(
+                            "Invalid value for env in [{}]: expected NAME=VALUE, got {}",
+                            group,
+                            variable
+                        )
+                    })
+                })
+                .collect::<Result<_>>()?;
+        }
+        if let Some(names) = optional_string_list(keyfile, group, "unset-env")? {
+            self.launch.unset_env = names
+                .iter()
+                .map(|name| name.trim())
+                .filter(|name| !name.is_empty())
+                .map(ToString::to_string)
+                .collect();
+        }
+        if let Some(args) = optional_string_list(keyfile, group, "args")? {
+            self.launch.args = args;
+        }
+        Ok(self)
+    }
+}
+
+/// Configuration of the service.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// How long a search waits for recent projects to load.
+    ///
+    /// `None` to wait indefinitely.
+    pub load_timeout: Option<Duration>,
+    /// Directories whose paths do not match search terms.
+    ///
+    /// The home directory never matches, regardless of this setting.
+    pub roots: Vec<String>,
+    /// Where to look for names of projects, in order.
+    pub name_sources: Vec<NameSource>,
+    /// Settings for all search providers.
+    pub provider: ProviderConfig,
+    /// Settings for individual search providers, by desktop ID.
+    pub providers: HashMap<String, ProviderConfig>,
+    /// Whether and how to move launched IDEs to systemd scopes.
+    pub scope_mode: ScopeMode,
+    /// How long the service waits for calls before it exits.
+    ///
+    /// `None` to never exit.  D-Bus starts the service again for the next call.
+    pub idle_timeout: Option<Duration>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            load_timeout: Some(Duration::from_secs(2)),
+            roots: Vec::new(),
+            name_sources: DEFAULT_NAME_SOURCES.to_vec(),
+            provider: ProviderConfig::default(),
+            providers: HashMap::new(),
+            scope_mode: ScopeMode::default(),
+            idle_timeout: None,
+        }
+    }
+}
+
+/// Get an optional integer from `key` in `group` of the given key file.
+///
+/// Return `None` if the key doesn't exist, and fail if it exists but doesn't hold an integer.
+fn optional_integer(keyfile: &glib::KeyFile, group: &str, key: &str) -> Result<Option<i32>> {
+    if keyfile.has_group(group) && keyfile.has_key(group, key)? {
+        keyfile
+            .integer(group, key)
+            .map(Some)
+            .with_context(|| format!("Invalid value for {} in [{}]", key, group))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Get an optional non-negative integer from `key` in `group` of the given key file.
+///
+/// Fail if the key exists, but doesn't hold an integer, or holds a negative integer.
+fn optional_unsigned(keyfile: &glib::KeyFile, group: &str, key: &str) -> Result<Option<u64>> {
+    optional_integer(keyfile, group, key)?
+        .map(|value| {
+            u64::try_from(value).map_err(|_| {
+                anyhow!(
+                    "Invalid value for {} in [{}]: {} is negative",
+                    key,
+                    group,
+                    value
+                )
+            })
+        })
+        .transpose()
+}
+
+/// Get an optional floating point number from `key` in `group` of the given key file.
+///
+/// Return `None` if the key doesn't exist, and fail if it exists but doesn't hold a number.
+fn optional_double(keyfile: &glib::KeyFile, group: &str, key: &str) -> Result<Option<f64>> {
+    if keyfile.has_group(group) && keyfile.has_key(group, key)? {
+        keyfile
+            .double(group, key)
+            .map(Some)
+            .with_context(|| format!("Invalid value for {} in [{}]", key, group))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Get an optional string from `key` in `group` of the given key file.
+///
+/// Return `None` if the key doesn't exist.
+fn optional_string(keyfile: &glib::KeyFile, group: &str, key: &str) -> Result<Option<String>> {
+    if keyfile.has_group(group) && keyfile.has_key(group, key)? {
+        keyfile
+            .string(group, key)
+            .map(|value| Some(value.to_string()))
+            .with_context(|| format!("Invalid value for {} in [{}]", key, group))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Parse a memory size in bytes, with an optional suffix `K`, `M`, `G` or `T` to the base 1024.
+///
+/// This follows the format of sizes in `systemd.resource-control(5)`.
+fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let (number, exponent) = match value.char_indices().last()? {
+        (i, 'K') => (&value[..i], 1),
+        (i, 'M') => (&value[..i], 2),
+        (i, 'G') => (&value[..i], 3),
+        (i, 'T') => (&value[..i], 4),
+        _ => (value, 0),
+    };
+    number
+        .parse::<u64>()
+        .ok()?
+        .checked_mul(1024_u64.pow(exponent))
+}
+
+/// Get an optional memory size in bytes from `key` in `group` of the given key file.
+///
+/// See [`parse_size`] for the format of the value.
+fn optional_size(keyfile: &glib::KeyFile, group: &str, key: &str) -> Result<Option<u64>> {
+    optional_string(keyfile, group, key)?
+        .map(|value| {
+            parse_size(&value).ok_or_else(|| {
+                anyhow!(
+                    "Invalid value for {} in [{}]: {} is not a size",
+                    key,
+                    group,
+                    value
+                )
+            })
+        })
+        .transpose()
+}
+
+/// Get an optional weight between 1 and 10000 from `key` in `group` of the given key file.
+fn optional_weight(keyfile: &glib::KeyFile, group: &str, key: &str) -> Result<Option<u64>> {
+    match optional_integer(keyfile, group, key)? {
+        Some(weight) if (1..=10000).contains(&weight) => Ok(Some(weight as u64)),
+        Some(weight) => Err(anyhow!(
+            "Invalid value for {} in [{}]: {} is not between 1 and 10000",
+            key,
+            group,
+            weight
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Parse an environment variable `NAME=VALUE` into its name and its value.
+fn parse_variable(value: &str) -> Option<(String, String)> {
+    let (name, value) = value.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        None
+    } else {
+        Some((name.to_string(), value.to_string()))
+    }
+}
+
+/// Parse a scope mode, i.e. `disabled`, `fallback` or `retry`.
+fn parse_scope_mode(value: &str) -> Option<ScopeMode> {
+    match value.trim() {
+        "disabled" => Some(ScopeMode::Disabled),
+        "fallback" => Some(ScopeMode::Fallback),
+        "retry" => Some(ScopeMode::Retry),
+        _ => None,
+    }
+}
+
+/// Get an optional list of strings from `key` in `group` of the given key file.
+///
+/// Return `None` if the key doesn't exist.
+fn optional_string_list(
+    keyfile: &glib::KeyFile,
+    group: &str,
+    key: &str,
+) -> Result<Option<Vec<String>>> {
+    if keyfile.has_group(group) && keyfile.has_key(group, key)? {
+        let values = keyfile
+            .string_list(group, key)
+            .with_context(|| format!("Invalid value for {} in [{}]", key, group))?;
+        Ok(Some(values.iter().map(ToString::to_string).collect()))
+    } else {
+        Ok(None)
+    }
+}
+
+impl Config {
+    /// The default location of the configuration file.
+    pub fn default_path() -> PathBuf {
+        glib::user_config_dir()
+            .join(env!("CARGO_PKG_NAME"))
+            .join("config.ini")
+    }
+
+    /// Read configuration from `keyfile`.
+    ///
+    /// Keys missing from `keyfile` take their default values.
+    pub fn from_key_file(keyfile: &glib::KeyFile) -> Result<Self> {
+        let mut config = Config::default();
+        if let Some(timeout) = optional_unsigned(keyfile, SEARCH_GROUP, "load-timeout-ms")? {
+            config.load_timeout = (0 < timeout).then(|| Duration::from_millis(timeout));
+        }
+        if let Some(roots) = optional_string_list(keyfile, SEARCH_GROUP, "root-prefixes")? {
+            config.roots = roots.iter().map(|root| expand_home(root.trim())).collect();
+        }
+        if let Some(sources) = optional_string_list(keyfile, PROJECTS_GROUP, "name-sources")? {
+            config.name_sources = sources
+                .iter()
+                .map(|source| source.trim().parse())
+                .collect::<Result<Vec<NameSource>>>()
+                .with_context(|| {
+                    format!("Invalid value for name-sources in [{}]", PROJECTS_GROUP)
+                })?;
+        }
+        if let Some(mode) = optional_string(keyfile, SCOPE_GROUP, "mode")? {
+            config.scope_mode = parse_scope_mode(&mode).ok_or_else(|| {
+                anyhow!(
+                    "Invalid value for mode in [{}]: expected disabled, fallback or retry, got {}",
+                    SCOPE_GROUP,
+                    mode
+                )
+            })?;
+        }
+        if let Some(timeout) = optional_unsigned(keyfile, SERVICE_GROUP, "idle-timeout-s")? {
+            config.idle_timeout = (0 < timeout).then(|| Duration::from_secs(timeout));
+        }
+        config.provider = ProviderConfig::default()
+            .merge_key_file(keyfile, SEARCH_GROUP)?
+            .merge_scope_key_file(keyfile, SCOPE_GROUP)?
+            .merge_launch_key_file(keyfile, LAUNCH_GROUP)?;
+        for group in keyfile.groups().0 {
+            if let Some(desktop_id) = group.strip_prefix(PROVIDER_GROUP_PREFIX) {
+                let mut provider = config
+                    .provider
+                    .clone()
+                    .merge_key_file(keyfile, &group)?
+                    .merge_scope_key_file(keyfile, &group)?
+                    .merge_launch_key_file(keyfile, &group)?;
+                if let Some(cli) = optional_string(keyfile, &group, "cli")? {
+                    provider.cli = Some(expand_home(cli.trim()));
+                }
+                config.providers.insert(desktop_id.to_string(), provider);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Get the settings for the search provider of the app with the given `desktop_id`.
+    pub fn provider_config(&self, desktop_id: &str) -> &ProviderConfig {
+        self.providers.get(desktop_id).unwrap_or(&self.provider)
+    }
+
+    /// Load configuration from the file at `path`.
+    ///
+    /// Return the default configuration if `path` does not exist.
+    pub fn load_from(path: &Path) -> Result<Self> {
+        if path.exists() {
+            info!("Loading configuration from {}", path.display());
+            let keyfile = glib::KeyFile::new();
+            keyfile
+                .load_from_file(path, glib::KeyFileFlags::NONE)
+                .with_context(|| format!("Failed to read configuration from {}", path.display()))?;
+            Config::from_key_file(&keyfile)
+                .with_context(|| format!("Invalid configuration in {}", path.display()))
+        } else {
+            debug!(
+                "Configuration file {} does not exist, using defaults",
+                path.display()
+            );
+            Ok(Config::default())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn parse(data: &str) -> Result<Config> {
+        let keyfile = glib::KeyFile::new();
+        keyfile.load_from_data(data, glib::KeyFileFlags::NONE)?;
+        Config::from_key_file(&keyfile)
+    }
+
+    #[test]
+    fn empty_config() {
+        assert_eq!(parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn load_timeout() {
+        let config = parse("[search]\nload-timeout-ms=500\n").unwrap();
+        assert_eq!(config.load_timeout, Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn disable_load_timeout() {
+        let config = parse("[search]\nload-timeout-ms=0\n").unwrap();
+        assert_eq!(config.load_timeout, None);
+    }
+
+    #[test]
+    fn invalid_load_timeout() {
+        assert!(parse("[search]\nload-timeout-ms=soon\n").is_err());
+        assert!(parse("[search]\nload-timeout-ms=-1\n").is_err());
+    }
+
+    #[test]
+    fn root_prefixes() {
+        let config = parse("[search]\nroot-prefixes=~/Code/gh;/srv/code\n").unwrap();
+        let home = glib::home_dir();
+        assert_eq!(
+            config.roots,
+            vec![
+                home.join("Code").join("gh").to_string_lossy().to_string(),
+                "/srv/code".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn name_sources() {
+        let config = parse("[projects]\nname-sources=cargo;idea;directory\n").unwrap();
+        assert_eq!(
+            config.name_sources,
+            vec![NameSource::Cargo, NameSource::Idea, NameSource::Directory]
+        );
+    }
+
+    #[test]
+    fn invalid_name_sources() {
+        assert!(parse("[projects]\nname-sources=cargo;ant\n").is_err());
+    }
+
+    #[test]
+    fn idle_timeout() {
+        let config = parse("[service]\nidle-timeout-s=600\n").unwrap();
+        assert_eq!(config.idle_timeout, Some(Duration::from_secs(600)));
+        let config = parse("[service]\nidle-timeout-s=0\n").unwrap();
+        assert_eq!(config.idle_timeout, None);
+    }
+
+    #[test]
+    fn invalid_idle_timeout() {
+        assert!(parse("[service]\nidle-timeout-s=-1\n").is_err());
+        assert!(parse("[service]\nidle-timeout-s=never\n").is_err());
+    }
+
+    #[test]
+    fn result_limits() {
+        let config = parse("[search]\nmax-results=5\nmin-score=1.5\n").unwrap();
+        let expected = ProviderConfig {
+            max_results: Some(5),
+            min_score: 1.5,
+            scope: ScopeResources::default(),
+            launch: LaunchOptions::default(),
+            cli: None,
+        };
+        assert_eq!(config.provider, expected);
+        assert_eq!(config.provider_config("jetbrains-idea.desktop"), &expected);
+    }
+
+    #[test]
+    fn unlimited_results() {
+        let config = parse("[search]\nmax-results=0\n").unwrap();
+        assert_eq!(config.provider.max_results, None);
+    }
+
+    #[test]
+    fn invalid_min_score() {
+        assert!(parse("[search]\nmin-score=high\n").is_err());
+    }
+
+    #[test]
+    fn provider_overrides() {
+        let config = parse(
+            "[search]\nmax-results=5\nmin-score=1.5\n\n[provider:jetbrains-idea.desktop]\nmax-results=10\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.provider_config("jetbrains-idea.desktop"),
+            &ProviderConfig {
+                max_results: Some(10),
+                min_score: 1.5,
+                scope: ScopeResources::default(),
+                launch: LaunchOptions::default(),
+                cli: None,
+            }
+        );
+        assert_eq!(
+            config.provider_config("jetbrains-clion.desktop"),
+            &ProviderConfig {
+                max_results: Some(5),
+                min_score: 1.5,
+                scope: ScopeResources::default(),
+                launch: LaunchOptions::default(),
+                cli: None,
+            }
+        );
+    }
+
+    #[test]
+    fn provider_cli() {
+        let config = parse(
+            "[search]\ncli=/usr/bin/ide\n\n[provider:jetbrains-idea.desktop]\ncli=~/.local/bin/idea\n",
+        )
+        .unwrap();
+        assert_eq!(config.provider.cli, None);
+        assert_eq!(
+            config.provider_config("jetbrains-idea.desktop").cli,
+            Some(expand_home("~/.local/bin/idea"))
+        );
+        assert_eq!(config.provider_config("jetbrains-clion.desktop").cli, None);
+    }
+
+    #[test]
+    fn launch_options() {
+        let config = parse(
+            "[launch]\nenv=_JAVA_OPTIONS=-Dawt.toolkit.name=WLToolkit;EMPTY=\nunset-env=JAVA_HOME\n\n[provider:jetbrains-idea.desktop]\nenv=IDEA_JDK=/usr/lib/jvm/java-17\nargs=-Dfoo=bar;--wayland\n",
+        )
+        .unwrap();
+        let expected = LaunchOptions {
+            env: vec![
+                (
+                    "_JAVA_OPTIONS".to_string(),
+                    "-Dawt.toolkit.name=WLToolkit".to_string(),
+                ),
+                ("EMPTY".to_string(), String::new()),
+            ],
+            unset_env: vec!["JAVA_HOME".to_string()],
+            args: Vec::new(),
+        };
+        assert_eq!(
+            config.provider_config("jetbrains-clion.desktop").launch,
+            expected
+        );
+        assert_eq!(
+            config.provider_config("jetbrains-idea.desktop").launch,
+            LaunchOptions {
+                env: vec![("IDEA_JDK".to_string(), "/usr/lib/jvm/java-17".to_string())],
+                args: vec!["-Dfoo=bar".to_string(), "--wayland".to_string()],
+                ..expected
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_env() {
+        assert!(parse("[launch]\nenv=JAVA_HOME\n").is_err());
+        assert!(parse("[launch]\nenv==foo\n").is_err());
+        assert!(parse("[provider:jetbrains-idea.desktop]\nenv=MY VAR=foo\n").is_err());
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("4K"), Some(4 * 1024));
+        assert_eq!(parse_size("6G"), Some(6 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size(" 1T "), Some(1024 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("G"), None);
+        assert_eq!(parse_size("6GB"), None);
+        assert_eq!(parse_size("-1"), None);
+        assert_eq!(parse_size("99999999999T"), None);
+    }
+
+    #[test]
+    fn scope_resources() {
+        let config = parse(
+            "[scope]\nslice=app-jetbrains.slice\nmemory-high=6G\nmemory-max=8G\ncpu-weight=50\n\n[provider:jetbrains-idea.desktop]\nmemory-max=12G\nio-weight=20\n",
+        )
+        .unwrap();
+        let expected = ScopeResources {
+            slice: Some("app-jetbrains.slice".to_string()),
+            memory_high: Some(6 * 1024 * 1024 * 1024),
+            memory_max: Some(8 * 1024 * 1024 * 1024),
+            cpu_weight: Some(50),
+            io_weight: None,
+        };
+        assert_eq!(
+            config.provider_config("jetbrains-clion.desktop").scope,
+            expected
+        );
+        assert_eq!(
+            config.provider_config("jetbrains-idea.desktop").scope,
+            ScopeResources {
+                memory_max: Some(12 * 1024 * 1024 * 1024),
+                io_weight: Some(20),
+                ..expected
+            }
+        );
+    }
+
+    #[test]
+    fn scope_mode() {
+        assert_eq!(parse("").unwrap().scope_mode, ScopeMode::Fallback);
+        let config = parse("[scope]\nmode=retry\n").unwrap();
+        assert_eq!(config.scope_mode, ScopeMode::Retry);
+        let config = parse("[scope]\nmode=disabled\n").unwrap();
+        assert_eq!(config.scope_mode, ScopeMode::Disabled);
+        assert!(parse("[scope]\nmode=sometimes\n").is_err());
+    }
+
+    #[test]
+    fn invalid_scope_resources() {
+        assert!(parse("[scope]\nslice=jetbrains\n").is_err());
+        assert!(parse("[scope]\nmemory-max=lots\n").is_err());
+        assert!(parse("[scope]\ncpu-weight=0\n").is_err());
+        assert!(parse("[provider:jetbrains-idea.desktop]\nio-weight=10001\n").is_err());
+    }
+}
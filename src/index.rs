@@ -0,0 +1,364 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A persistent index of recent projects, for instant results right after startup.
+//!
+//! The index of each IDE lives in `$XDG_CACHE_HOME/gnome-search-providers-jetbrains/`, in the
+//! format of Glib's key files, i.e.
+//!
+//! ```ini
+//! [index]
+//! version=2
+//! name-sources=idea;gradle;directory
+//! source=/home/foo/.config/JetBrains/IntelliJIdea2021.3/options/recentProjects.xml
+//! source-mtime=1641819636123456
+//!
+//! [project:0]
+//! name=mdcat
+//! uri=/home/foo/Code/mdcat
+//! keywords=cargo;rust
+//! ```
+//!
+//! The index records the file of recent projects it was built from, and its modification time,
+//! to tell whether the index is still up to date, as well as the sources project names came from,
+//! to discard the index when these change.  It does not track files project names and keywords
+//! come from.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use tracing::{debug, info};
+
+use gnome_search_provider_common::futures_util::future::{FutureExt, LocalBoxFuture};
+
+use gnome_search_provider_common::app::*;
+use gnome_search_provider_common::gio;
+use gnome_search_provider_common::gio::glib;
+use gnome_search_provider_common::gio::prelude::*;
+use gnome_search_provider_common::source::IdMap;
+
+use crate::project::NameSource;
+
+/// The version of the index format.
+///
+/// Increment when changing the format, or what goes into projects, to discard old indexes.
+const INDEX_VERSION: i32 = 2;
+
+/// The group with information about the index itself.
+const INDEX_GROUP: &str = "index";
+
+/// The prefix of groups for projects.
+const PROJECT_GROUP_PREFIX: &str = "project:";
+
+/// A file at a given modification time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceStamp {
+    /// The path of the file.
+    pub path: PathBuf,
+    /// The modification time of the file, in microseconds since the epoch.
+    pub mtime: u64,
+}
+
+impl SourceStamp {
+    /// Get the stamp of the file at `path`, at its current modification time.
+    pub async fn of_file(path: PathBuf) -> Result<Self> {
+        let file = gio::File::for_path(&path);
+        let info = file
+            .query_info_async_future(
+                &format!(
+                    "{},{}",
+                    *gio::FILE_ATTRIBUTE_TIME_MODIFIED,
+                    *gio::FILE_ATTRIBUTE_TIME_MODIFIED_USEC
+                ),
+                gio::FileQueryInfoFlags::NONE,
+                glib::PRIORITY_DEFAULT,
+            )
+            .await
+            .with_context(|| format!("Failed to query modification time of {}", file.uri()))?;
+        let mtime = info.attribute_uint64(&gio::FILE_ATTRIBUTE_TIME_MODIFIED) * 1_000_000
+            + u64::from(info.attribute_uint32(&gio::FILE_ATTRIBUTE_TIME_MODIFIED_USEC));
+        Ok(Self { path, mtime })
+    }
+}
+
+/// Escape `value` for a list value in a key file.
+fn escape_list_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(';', "\\;")
+}
+
+/// Create `directory` and all its missing parents.
+fn create_dir_all(directory: gio::File) -> LocalBoxFuture<'static, Result<(), glib::Error>> {
+    async move {
+        match directory
+            .make_directory_async_future(glib::PRIORITY_DEFAULT)
+            .await
+        {
+            Err(error) if error.matches(gio::IOErrorEnum::Exists) => Ok(()),
+            Err(error) if error.matches(gio::IOErrorEnum::NotFound) => match directory.parent() {
+                Some(parent) => {
+                    create_dir_all(parent).await?;
+                    directory
+                        .make_directory_async_future(glib::PRIORITY_DEFAULT)
+                        .await
+                }
+                None => Err(error),
+            },
+            result => result,
+        }
+    }
+    .boxed_local()
+}
+
+/// An index of recent projects of an IDE.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectsIndex {
+    /// The file recent projects were read from.
+    pub stamp: SourceStamp,
+    /// Where names of projects came from, in order.
+    pub name_sources: Vec<NameSource>,
+    /// The recent projects, in order.
+    pub projects: Vec<AppLaunchItem>,
+}
+
+impl ProjectsIndex {
+    /// The location of the index for the app with the given `app_id`.
+    pub fn default_path(app_id: &AppId) -> PathBuf {
+        glib::user_cache_dir()
+            .join(env!("CARGO_PKG_NAME"))
+            .join(format!("{}.index", app_id))
+    }
+
+    /// The projects of this index as items of the app with the given `app_id`.
+    pub fn items(&self, app_id: &AppId) -> IdMap<AppLaunchItem> {
+        self.projects
+            .iter()
+            .map(|project| {
+                let id = AppItemId::new(app_id.clone(), project.uri().to_string());
+                (id.to_string(), project.clone())
+            })
+            .collect()
+    }
+
+    /// Write this index to a key file.
+    pub fn to_key_file(&self) -> glib::KeyFile {
+        let keyfile = glib::KeyFile::new();
+        keyfile.set_integer(INDEX_GROUP, "version", INDEX_VERSION);
+        let name_sources: Vec<String> = self.name_sources.iter().map(ToString::to_string).collect();
+        keyfile.set_value(INDEX_GROUP, "name-sources", &name_sources.join(";"));
+        keyfile.set_string(INDEX_GROUP, "source", &self.stamp.path.to_string_lossy());
+        keyfile.set_uint64(INDEX_GROUP, "source-mtime", self.stamp.mtime);
+        for (index, project) in self.projects.iter().enumerate() {
+            let group = format!("{}{}", PROJECT_GROUP_PREFIX, index);
+            keyfile.set_string(&group, "name", project.name());
+            keyfile.set_string(&group, "uri", project.uri());
+            let keywords: Vec<String> = project
+                .keywords()
+                .iter()
+                .map(|k| escape_list_value(k))
+                .collect();
+            keyfile.set_value(&group, "keywords", &keywords.join(";"));
+        }
+        keyfile
+    }
+
+    /// Read an index from `keyfile`.
+    ///
+    /// Fail if `keyfile` has a different version.
+    pub fn from_key_file(keyfile: &glib::KeyFile) -> Result<Self> {
+        let version = keyfile
+            .integer(INDEX_GROUP, "version")
+            .with_context(|| "Missing index version")?;
+        if version != INDEX_VERSION {
+            return Err(anyhow!(
+                "Unsupported index version {}, expected {}",
+                version,
+                INDEX_VERSION
+            ));
+        }
+        let name_sources = keyfile
+            .string_list(INDEX_GROUP, "name-sources")?
+            .iter()
+            .map(|source| source.parse())
+            .collect::<Result<Vec<NameSource>>>()?;
+        let stamp = SourceStamp {
+            path: PathBuf::from(keyfile.string(INDEX_GROUP, "source")?.as_str()),
+            mtime: keyfile.uint64(INDEX_GROUP, "source-mtime")?,
+        };
+        let mut groups: Vec<(usize, glib::GString)> = keyfile
+            .groups()
+            .0
+            .into_iter()
+            .filter_map(|group| {
+                group
+                    .strip_prefix(PROJECT_GROUP_PREFIX)
+                    .and_then(|index| index.parse().ok())
+                    .map(|index| (index, group.clone()))
+            })
+            .collect();
+        groups.sort_by_key(|(index, _)| *index);
+        let projects = groups
+            .iter()
+            .map(|(_, group)| {
+                Ok(AppLaunchItem::new(
+                    keyfile.string(group, "name")?.to_string(),
+                    keyfile.string(group, "uri")?.to_string(),
+                    keyfile
+                        .string_list(group, "keywords")?
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect(),
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            stamp,
+            name_sources,
+            projects,
+        })
+    }
+
+    /// Load the index from the file at `path`.
+    ///
+    /// Return `None` if `path` doesn't exist.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if path.exists() {
+            let keyfile = glib::KeyFile::new();
+            keyfile
+                .load_from_file(path, glib::KeyFileFlags::NONE)
+                .with_context(|| format!("Failed to read index from {}", path.display()))?;
+            let index = ProjectsIndex::from_key_file(&keyfile)
+                .with_context(|| format!("Invalid index in {}", path.display()))?;
+            info!(
+                "Loaded {} project(s) from index at {}",
+                index.projects.len(),
+                path.display()
+            );
+            Ok(Some(index))
+        } else {
+            debug!("No index at {}", path.display());
+            Ok(None)
+        }
+    }
+
+    /// Save this index to the file at `path`.
+    ///
+    /// Gio writes to a temporary file first and moves it to `path` afterwards, so that `path`
+    /// always holds a complete index.
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        if let Some(directory) = path.parent() {
+            create_dir_all(gio::File::for_path(directory))
+                .await
+                .with_context(|| format!("Failed to create {}", directory.display()))?;
+        }
+        gio::File::for_path(path)
+            .replace_contents_async_future(
+                self.to_key_file().to_data().to_string(),
+                None,
+                false,
+                gio::FileCreateFlags::REPLACE_DESTINATION,
+            )
+            .await
+            .map_err(|(_, error)| error)
+            .with_context(|| format!("Failed to write index to {}", path.display()))?;
+        debug!(
+            "Saved {} project(s) to index at {}",
+            self.projects.len(),
+            path.display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use gnome_search_provider_common::testutil::{block_on, TempDir};
+
+    use super::*;
+
+    fn index() -> ProjectsIndex {
+        ProjectsIndex {
+            stamp: SourceStamp {
+                path: PathBuf::from(
+                    "/home/foo/.config/JetBrains/IdeaIC2021.3/options/recentProjects.xml",
+                ),
+                mtime: 1641819636123456,
+            },
+            name_sources: vec![NameSource::Idea, NameSource::Cargo, NameSource::Directory],
+            projects: vec![
+                AppLaunchItem::new(
+                    "mdcat".to_string(),
+                    "/home/foo/Code/mdcat".to_string(),
+                    vec!["cargo".to_string(), "rust".to_string()],
+                ),
+                AppLaunchItem::new(
+                    "odd; name".to_string(),
+                    "/home/foo/Code/odd".to_string(),
+                    vec!["semi;colon".to_string(), "back\\slash".to_string()],
+                ),
+                AppLaunchItem::new(
+                    "plain".to_string(),
+                    "/home/foo/Code/plain".to_string(),
+                    Vec::new(),
+                ),
+            ],
+        }
+    }
+
+    fn roundtrip(index: &ProjectsIndex) -> Result<ProjectsIndex> {
+        let keyfile = glib::KeyFile::new();
+        keyfile.load_from_data(&index.to_key_file().to_data(), glib::KeyFileFlags::NONE)?;
+        ProjectsIndex::from_key_file(&keyfile)
+    }
+
+    #[test]
+    fn roundtrip_index() {
+        let index = index();
+        assert_eq!(roundtrip(&index).unwrap(), index);
+    }
+
+    #[test]
+    fn reject_other_versions() {
+        let keyfile = index().to_key_file();
+        keyfile.set_integer(INDEX_GROUP, "version", INDEX_VERSION + 1);
+        assert!(ProjectsIndex::from_key_file(&keyfile).is_err());
+    }
+
+    #[test]
+    fn reject_unknown_name_sources() {
+        let keyfile = index().to_key_file();
+        keyfile.set_value(INDEX_GROUP, "name-sources", "idea;nonsense");
+        assert!(ProjectsIndex::from_key_file(&keyfile).is_err());
+    }
+
+    #[test]
+    fn items_of_index() {
+        let app_id = AppId::from("jetbrains-idea-ce.desktop");
+        let items = index().items(&app_id);
+        let ids: Vec<String> = items.keys().cloned().collect();
+        assert_eq!(
+            ids,
+            [
+                AppItemId::new(app_id.clone(), "/home/foo/Code/mdcat".to_string()).to_string(),
+                AppItemId::new(app_id.clone(), "/home/foo/Code/odd".to_string()).to_string(),
+                AppItemId::new(app_id.clone(), "/home/foo/Code/plain".to_string()).to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn save_and_load() {
+        let directory = TempDir::new();
+        let path = directory
+            .path()
+            .join("cache")
+            .join("jetbrains-idea.desktop.index");
+        assert_eq!(ProjectsIndex::load(&path).unwrap(), None);
+        block_on(index().save(&path)).unwrap();
+        assert_eq!(ProjectsIndex::load(&path).unwrap(), Some(index()));
+    }
+}
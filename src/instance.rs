@@ -0,0 +1,286 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Open projects in running instances of Jetbrains IDEs.
+//!
+//! A running IDE keeps a `.port` file in its configuration directory, with the port it listens
+//! on for requests from the command line launcher of the IDE.  If the launcher finds a running
+//! instance, it hands the project over and exits, instead of starting another JVM.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use tracing::{debug, instrument, warn, Span};
+use tracing_futures::Instrument;
+
+use gnome_search_provider_common::app::{
+    AppId, AppLaunchClient, RunningInstance, RunningInstanceError,
+};
+use gnome_search_provider_common::futures_channel;
+use gnome_search_provider_common::gio;
+use gnome_search_provider_common::gio::glib;
+use gnome_search_provider_common::gio::prelude::*;
+
+use crate::{parse_open_jetbrains_projects, ConfigLocation};
+
+/// Parse the contents of a `.port` file.
+fn parse_port(contents: &str) -> Option<u16> {
+    contents.trim().parse().ok().filter(|port| 0 < *port)
+}
+
+/// Read the contents of `path`, or return `None` if `path` doesn't exist.
+async fn read_optional_file(path: &Path) -> Result<Option<String>> {
+    let file = gio::File::for_path(path);
+    match file.load_contents_async_future().await {
+        Ok((data, _)) => Ok(Some(String::from_utf8_lossy(&data).into_owned())),
+        Err(error) if error.matches(gio::IOErrorEnum::NotFound) => Ok(None),
+        Err(error) => Err(error).with_context(|| format!("Failed to read {}", file.uri())),
+    }
+}
+
+/// Whether something listens on `port` on the loopback interface.
+async fn is_listening(port: u16) -> bool {
+    match gio::SocketClient::new()
+        .connect_to_host_async_future("127.0.0.1", port)
+        .await
+    {
+        Ok(_) => true,
+        Err(error) => {
+            debug!("Failed to connect to port {}: {}", port, error);
+            false
+        }
+    }
+}
+
+/// Whether an instance of the IDE with the given `config` runs and has `uri` open, or can open it.
+///
+/// An instance runs if the `.port` file in the latest configuration directory refers to a port
+/// which accepts connections.  Without a `.port` file, assume that an instance runs if the
+/// configuration directory is locked, and the IDE marked `uri` as open in its recent projects.
+#[instrument]
+async fn is_running(config: &ConfigLocation<'_>, uri: &str) -> Result<bool> {
+    let config_dir = config
+        .find_config_dir_of_latest_version(&glib::user_config_dir())
+        .await?
+        .into_path();
+    let port_file = config_dir.join(".port");
+    if let Some(contents) = read_optional_file(&port_file).await? {
+        return match parse_port(&contents) {
+            Some(port) if is_listening(port).await => Ok(true),
+            Some(port) => {
+                debug!(
+                    "Nothing listens on port {} from {}, instance not running",
+                    port,
+                    port_file.display()
+                );
+                Ok(false)
+            }
+            None => Err(anyhow!(
+                "Invalid port {:?} in {}",
+                contents,
+                port_file.display()
+            )),
+        };
+    }
+    if !config_dir.join(".lock").exists() {
+        debug!("{} neither has a port nor a lock", config_dir.display());
+        return Ok(false);
+    }
+    let projects_file = config_dir.join("options").join(config.projects_filename);
+    let open = match read_optional_file(&projects_file).await? {
+        Some(contents) => parse_open_jetbrains_projects(contents.as_bytes())?,
+        None => Vec::new(),
+    };
+    debug!(
+        "{} locked, with open projects {:?}",
+        config_dir.display(),
+        open
+    );
+    Ok(open.iter().any(|project| project == uri))
+}
+
+/// Find the command line launcher `cli` in `directories`.
+///
+/// If `cli` is a path use it directly, otherwise look for `cli` in each of `directories`, in order.
+fn find_cli_in(cli: &str, directories: &[PathBuf]) -> Option<PathBuf> {
+    if cli.contains('/') {
+        Some(PathBuf::from(cli)).filter(|path| path.is_file())
+    } else {
+        directories
+            .iter()
+            .map(|directory| directory.join(cli))
+            .find(|path| path.is_file())
+    }
+}
+
+/// Find the command line launcher `cli`.
+///
+/// If `cli` is a path use it directly, otherwise look for `cli` in `$PATH` and then in the
+/// directory of shell scripts of the Toolbox App.
+fn find_cli(cli: &str) -> Option<PathBuf> {
+    let mut directories: Vec<PathBuf> = std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).collect())
+        .unwrap_or_default();
+    directories.push(
+        glib::user_data_dir()
+            .join("JetBrains")
+            .join("Toolbox")
+            .join("scripts"),
+    );
+    find_cli_in(cli, &directories)
+}
+
+/// Open `uri` with the command line launcher at `cli` of `app_id`.
+///
+/// Run the launcher through `launcher`, which moves it to a scope of `app_id`, and return once
+/// it's in its scope.  Don't wait for the launcher to exit: if the instance shut down in the
+/// meantime the launcher starts a new instance which then opens `uri`, and keeps running in its
+/// scope.
+async fn open_with_cli(
+    launcher: &AppLaunchClient,
+    app_id: &AppId,
+    cli: &Path,
+    uri: &str,
+) -> Result<()> {
+    debug!("Opening {} with {}", uri, cli.display());
+    let argv = vec![cli.as_os_str().to_owned(), OsString::from(uri)];
+    launcher
+        .run_command(app_id.clone(), argv, Some(uri.to_string()))
+        .await
+        .with_context(|| format!("{} failed to open {}", cli.display(), uri))
+}
+
+/// Open `uri` in a running instance of the IDE `app_id` with the given `config` through `cli`.
+///
+/// Run `cli` through `launcher`.  Return whether an instance runs, and fail if an instance runs
+/// but `cli` couldn't open `uri`.  If it's unclear whether an instance runs assume it doesn't.
+async fn open_in_running_instance(
+    launcher: &AppLaunchClient,
+    config: &ConfigLocation<'_>,
+    app_id: &AppId,
+    cli: &str,
+    uri: &str,
+) -> Result<bool> {
+    match is_running(config, uri).await {
+        Ok(true) => {}
+        Ok(false) => return Ok(false),
+        Err(error) => {
+            warn!(
+                "Failed to find out whether {} runs, assuming it doesn't: {:#}",
+                app_id, error
+            );
+            return Ok(false);
+        }
+    }
+    let cli_path = find_cli(cli).with_context(|| {
+        format!(
+            "{} runs, but its command line launcher {} was not found",
+            app_id, cli
+        )
+    })?;
+    open_with_cli(launcher, app_id, &cli_path, uri).await?;
+    Ok(true)
+}
+
+/// A running instance of a Jetbrains IDE.
+#[derive(Debug)]
+pub struct JetbrainsInstance {
+    /// The desktop ID of the IDE.
+    app_id: AppId,
+    /// Where to look for the configuration of the IDE.
+    config: &'static ConfigLocation<'static>,
+    /// The name of or the path to the command line launcher of the IDE.
+    cli: String,
+}
+
+impl JetbrainsInstance {
+    /// Open projects in a running instance of the IDE `app_id` through its launcher `cli`.
+    ///
+    /// `config` tells where to look for the configuration of the IDE, to find out whether an
+    /// instance runs.
+    pub fn new(app_id: AppId, config: &'static ConfigLocation<'static>, cli: String) -> Self {
+        Self {
+            app_id,
+            config,
+            cli,
+        }
+    }
+}
+
+#[async_trait]
+impl RunningInstance for JetbrainsInstance {
+    #[instrument(skip(launcher))]
+    async fn open_uri(
+        &self,
+        launcher: &AppLaunchClient,
+        uri: &str,
+    ) -> Result<bool, RunningInstanceError> {
+        let (send, recv) = futures_channel::oneshot::channel();
+        let launcher = launcher.clone();
+        let app_id = self.app_id.clone();
+        let config = self.config;
+        let cli = self.cli.clone();
+        let uri = uri.to_string();
+        let span = Span::current();
+        // Gio futures aren't Send, so move to the main thread, and get the result sent back.
+        glib::MainContext::default().invoke(move || {
+            glib::MainContext::default().spawn_local(
+                async move {
+                    let result =
+                        open_in_running_instance(&launcher, config, &app_id, &cli, &uri).await;
+                    // We don't care if the receiver went away
+                    let _ = send.send(result);
+                }
+                .instrument(span),
+            );
+        });
+        recv.await
+            .unwrap_or_else(|_| Err(anyhow!("Main context dropped the request")))
+            .map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use gnome_search_provider_common::testutil::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn port() {
+        assert_eq!(parse_port("63342\n"), Some(63342));
+        assert_eq!(parse_port("0"), None);
+        assert_eq!(parse_port("localhost"), None);
+        assert_eq!(parse_port(""), None);
+    }
+
+    #[test]
+    fn cli_path() {
+        let directory = TempDir::new();
+        let bin = directory.create_dir("bin", &[("idea", "")]);
+        let scripts = directory.create_dir("scripts", &[("idea", ""), ("clion", "")]);
+        let directories = [bin.clone(), scripts.clone()];
+        let cli = bin.join("idea");
+        assert_eq!(
+            find_cli_in(cli.to_str().unwrap(), &directories),
+            Some(cli.clone())
+        );
+        assert_eq!(
+            find_cli_in(bin.join("clion").to_str().unwrap(), &directories),
+            None
+        );
+        assert_eq!(find_cli_in("idea", &directories), Some(cli));
+        assert_eq!(
+            find_cli_in("clion", &directories),
+            Some(scripts.join("clion"))
+        );
+        assert_eq!(find_cli_in("goland", &directories), None);
+    }
+}
@@ -12,27 +12,43 @@ use std::ffi::OsStr;
 use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
 use elementtree::Element;
 use lazy_static::lazy_static;
 use regex::Regex;
-use tracing::{debug, error, info, trace};
+use tracing::{debug, error, info, trace, warn};
 use tracing::{instrument, Span};
 use tracing_futures::Instrument;
 
 use gnome_search_provider_common::app::*;
 use gnome_search_provider_common::futures_channel;
+use gnome_search_provider_common::futures_util::future::{join, select, Either};
+use gnome_search_provider_common::futures_util::stream::StreamExt;
 use gnome_search_provider_common::gio;
 use gnome_search_provider_common::gio::glib;
 use gnome_search_provider_common::gio::prelude::*;
+use gnome_search_provider_common::history::ActivationHistory;
 use gnome_search_provider_common::logging::*;
 use gnome_search_provider_common::mainloop::*;
-use gnome_search_provider_common::matching::*;
+use gnome_search_provider_common::notify::*;
+use gnome_search_provider_common::parking_lot::Mutex;
 use gnome_search_provider_common::source::*;
 use gnome_search_provider_common::zbus;
 
+use crate::config::Config;
+use crate::index::{ProjectsIndex, SourceStamp};
+use crate::instance::JetbrainsInstance;
+use crate::project::{detect_all_keywords, get_project_names, NameSource};
+
+mod config;
+mod index;
+mod instance;
+mod project;
+
 /// A path with an associated version.
 #[derive(Debug)]
 struct VersionedPath {
@@ -41,8 +57,10 @@ struct VersionedPath {
     version: (u16, u16),
 }
 
-/// Read paths of all recent projects from the given `reader`.
-fn parse_recent_jetbrains_projects<R: Read>(reader: R) -> Result<Vec<String>> {
+/// Read all entries of recent projects from the given `reader`.
+///
+/// Return the path of every recent project, and whether the IDE marked the project as open.
+fn parse_recent_jetbrains_entries<R: Read>(reader: R) -> Result<Vec<(String, bool)>> {
     let element = Element::from_reader(reader)?;
     let home = glib::home_dir()
         .into_os_string()
@@ -52,7 +70,7 @@ fn parse_recent_jetbrains_projects<R: Read>(reader: R) -> Result<Vec<String>> {
 
     trace!("Finding projects in {:?}", element);
 
-    let projects = element
+    let entries = element
         .find_all("component")
         .find(|e| {
             e.get_attr("name") == Some("RecentProjectsManager")
@@ -65,14 +83,44 @@ fn parse_recent_jetbrains_projects<R: Read>(reader: R) -> Result<Vec<String>> {
         .and_then(|opt| opt.find("map"))
         .map(|map| {
             map.find_all("entry")
-                .filter_map(|entry| entry.get_attr("key"))
-                .map(|key| key.replace("$USER_HOME$", &home))
+                .filter_map(|entry| {
+                    entry.get_attr("key").map(|key| {
+                        let opened = entry
+                            .find("value")
+                            .and_then(|value| value.children().next())
+                            .and_then(|meta| meta.get_attr("opened"))
+                            == Some("true");
+                        (key.replace("$USER_HOME$", &home), opened)
+                    })
+                })
                 .collect()
         })
         .unwrap_or_default();
 
-    trace!("Parsed projects {:?} from {:?}", projects, element);
+    Ok(entries)
+}
 
+/// Read paths of all recent projects from the given `reader`.
+fn parse_recent_jetbrains_projects<R: Read>(reader: R) -> Result<Vec<String>> {
+    let projects: Vec<String> = parse_recent_jetbrains_entries(reader)?
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect();
+    trace!("Parsed projects {:?}", projects);
+    Ok(projects)
+}
+
+/// Read paths of all recent projects which the IDE marked as open from the given `reader`.
+///
+/// The IDE only updates this mark when it saves its state, so the mark may be outdated, e.g. if
+/// the IDE crashed.
+fn parse_open_jetbrains_projects<R: Read>(reader: R) -> Result<Vec<String>> {
+    let projects: Vec<String> = parse_recent_jetbrains_entries(reader)?
+        .into_iter()
+        .filter(|(_, opened)| *opened)
+        .map(|(path, _)| path)
+        .collect();
+    trace!("Parsed open projects {:?}", projects);
     Ok(projects)
 }
 
@@ -176,41 +224,8 @@ impl ConfigLocation<'_> {
     }
 }
 
-/// Try to read the name of a Jetbrains project from the `name` file of the given project directory.
-///
-/// Look for a `name` file in the `.idea` sub-directory and return the contents of this file.
-async fn read_name_from_file<P: AsRef<Path>>(path: P) -> Result<String> {
-    let name_file = gio::File::for_path(path.as_ref().join(".idea").join(".name"));
-    trace!("Trying to read name from {}", name_file.uri());
-    let (data, _) = name_file
-        .load_contents_async_future()
-        .await
-        .with_context(|| format!("Failed to read project name from {}", name_file.uri()))?;
-    Ok(String::from_utf8_lossy(&data).trim().to_string())
-}
-
-/// Get the name of the Jetbrains product at the given path.
-///
-/// Look for a `name` file in the `.idea` sub-directory; if that file does not exist
-/// or cannot be read take the file name of `path`, and ultimately return `None` if
-/// the name cannot be determined.
-async fn get_project_name<P: AsRef<Path> + std::fmt::Debug>(path: P) -> Option<String> {
-    match read_name_from_file(path.as_ref()).await {
-        Ok(name) => Some(name),
-        Err(error) => {
-            debug!(
-                "Failed to read project name from file {:#}; falling back to file name of {}",
-                error,
-                path.as_ref().display()
-            );
-            path.as_ref()
-                .file_name()
-                .map(|name| name.to_string_lossy().to_string())
-        }
-    }
-}
-
 /// A search provider to expose from this service.
+#[derive(Debug)]
 struct ProviderDefinition<'a> {
     /// A human readable label for this provider.
     label: &'a str,
@@ -220,6 +235,10 @@ struct ProviderDefinition<'a> {
     relative_obj_path: &'a str,
     /// The location of the configuration of the corresponding product.
     config: ConfigLocation<'a>,
+    /// The name of the command line launcher of the product, as created by the Toolbox App.
+    ///
+    /// The launcher opens projects in a running instance of the product.
+    cli: &'a str,
 }
 
 impl ProviderDefinition<'_> {
@@ -248,6 +267,7 @@ const PROVIDERS: &[ProviderDefinition] = &[
             config_prefix: "CLion",
             projects_filename: "recentProjects.xml",
         },
+        cli: "clion",
     },
     ProviderDefinition {
         label: "GoLand (toolbox)",
@@ -258,6 +278,7 @@ const PROVIDERS: &[ProviderDefinition] = &[
             config_prefix: "GoLand",
             projects_filename: "recentProjects.xml",
         },
+        cli: "goland",
     },
     ProviderDefinition {
         label: "IDEA (toolbox)",
@@ -268,6 +289,7 @@ const PROVIDERS: &[ProviderDefinition] = &[
             config_prefix: "IntelliJIdea",
             projects_filename: "recentProjects.xml",
         },
+        cli: "idea",
     },
     ProviderDefinition {
         label: "IDEA Community Edition (toolbox)",
@@ -278,6 +300,7 @@ const PROVIDERS: &[ProviderDefinition] = &[
             config_prefix: "IdeaIC",
             projects_filename: "recentProjects.xml",
         },
+        cli: "idea-ce",
     },
     ProviderDefinition {
         label: "PHPStorm (toolbox)",
@@ -288,6 +311,7 @@ const PROVIDERS: &[ProviderDefinition] = &[
             config_prefix: "PhpStorm",
             projects_filename: "recentProjects.xml",
         },
+        cli: "phpstorm",
     },
     ProviderDefinition {
         label: "PyCharm (toolbox)",
@@ -298,6 +322,7 @@ const PROVIDERS: &[ProviderDefinition] = &[
             config_prefix: "PyCharm",
             projects_filename: "recentProjects.xml",
         },
+        cli: "pycharm",
     },
     ProviderDefinition {
         label: "Rider (toolbox)",
@@ -308,6 +333,7 @@ const PROVIDERS: &[ProviderDefinition] = &[
             config_prefix: "Rider",
             projects_filename: "recentSolutions.xml",
         },
+        cli: "rider",
     },
     ProviderDefinition {
         label: "RubyMine (toolbox)",
@@ -318,6 +344,7 @@ const PROVIDERS: &[ProviderDefinition] = &[
             config_prefix: "RubyMine",
             projects_filename: "recentProjects.xml",
         },
+        cli: "rubymine",
     },
     ProviderDefinition {
         label: "Android Studio (toolbox)",
@@ -328,6 +355,7 @@ const PROVIDERS: &[ProviderDefinition] = &[
             config_prefix: "AndroidStudio",
             projects_filename: "recentProjects.xml",
         },
+        cli: "studio",
     },
     ProviderDefinition {
         label: "WebStorm (toolbox)",
@@ -338,6 +366,7 @@ const PROVIDERS: &[ProviderDefinition] = &[
             config_prefix: "WebStorm",
             projects_filename: "recentProjects.xml",
         },
+        cli: "webstorm",
     },
 ];
 
@@ -346,20 +375,64 @@ struct JetbrainsProjectsSource<'a> {
     app_id: AppId,
     /// Where to look for the configuration and the list of recent projects.
     config: &'a ConfigLocation<'a>,
+    /// Where to look for project names, in order.
+    name_sources: Vec<NameSource>,
+    /// Where to save the index of recent projects.
+    index_path: PathBuf,
+    /// The index loaded at startup, until the first search uses it.
+    index: Mutex<Option<ProjectsIndex>>,
+    /// The index last loaded or saved, to skip saving an unchanged index.
+    saved_index: Arc<Mutex<Option<ProjectsIndex>>>,
+    /// Where to send a change if the index turns out to be outdated.
+    index_changes: Mutex<Option<futures_channel::mpsc::UnboundedSender<()>>>,
+}
+
+impl<'a> JetbrainsProjectsSource<'a> {
+    /// Create a source for recent projects of the app with the given `app_id`.
+    ///
+    /// Load the index of recent projects, to use for the first search, unless the index used
+    /// different `name_sources`.
+    fn new(app_id: AppId, config: &'a ConfigLocation<'a>, name_sources: Vec<NameSource>) -> Self {
+        let index_path = ProjectsIndex::default_path(&app_id);
+        let index = ProjectsIndex::load(&index_path)
+            .unwrap_or_else(|error| {
+                warn!("Ignoring index of {}: {:#}", app_id, error);
+                None
+            })
+            .filter(|index| {
+                let current = index.name_sources == name_sources;
+                if !current {
+                    info!("Ignoring index of {} with other name sources", app_id);
+                }
+                current
+            });
+        Self {
+            app_id,
+            config,
+            name_sources,
+            index_path,
+            saved_index: Arc::new(Mutex::new(index.clone())),
+            index: Mutex::new(index),
+            index_changes: Mutex::new(None),
+        }
+    }
 }
 
 #[instrument]
 async fn read_recent_items(
     config: &ConfigLocation<'_>,
     app_id: AppId,
-) -> Result<IdMap<AppLaunchItem>> {
+    name_sources: &[NameSource],
+) -> Result<ProjectsIndex> {
     info!("Searching recent projects for {}", app_id);
-    let mut items = IndexMap::new();
-    let projects_file = gio::File::for_path(
+    let mut projects = Vec::new();
+    let stamp = SourceStamp::of_file(
         config
             .find_latest_recent_projects_file(&glib::user_config_dir())
             .await?,
-    );
+    )
+    .await?;
+    let projects_file = gio::File::for_path(&stamp.path);
 
     let (data, _) = projects_file
         .load_contents_async_future()
@@ -371,23 +444,105 @@ async fn read_recent_items(
             )
         })?;
 
-    for path in parse_recent_jetbrains_projects(&*data)? {
-        if let Some(name) = get_project_name(&path).await {
+    let paths = parse_recent_jetbrains_projects(&*data)?;
+    let (names, keywords) = join(
+        get_project_names(paths.clone(), name_sources),
+        detect_all_keywords(&paths),
+    )
+    .await;
+    for ((path, name), keywords) in names.into_iter().zip(keywords) {
+        if let Some(name) = name {
             trace!("Found project {} at {} for {}", name, path, app_id);
-            let id = format!("jetbrains-recent-project-{}-{}", app_id, path);
-            items.insert(
-                id,
-                AppLaunchItem {
-                    name,
-                    uri: path.to_string(),
-                },
-            );
+            projects.push(AppLaunchItem::new(name, path, keywords));
         } else {
             trace!("Skipping {}, failed to determine project name", path);
         }
     }
-    info!("Found {} project(s) for {}", items.len(), app_id);
-    Ok(items)
+    info!("Found {} project(s) for {}", projects.len(), app_id);
+    Ok(ProjectsIndex {
+        stamp,
+        name_sources: name_sources.to_vec(),
+        projects,
+    })
+}
+
+/// Whether `stamp` still refers to the latest recent projects file in `config`.
+async fn is_index_current(config: &ConfigLocation<'_>, stamp: &SourceStamp) -> Result<bool> {
+    let path = config
+        .find_latest_recent_projects_file(&glib::user_config_dir())
+        .await?;
+    Ok(SourceStamp::of_file(path).await? == *stamp)
+}
+
+/// Whether `event` of a file monitor can change recent projects.
+fn is_relevant_change(event: gio::FileMonitorEvent) -> bool {
+    matches!(
+        event,
+        gio::FileMonitorEvent::ChangesDoneHint
+            | gio::FileMonitorEvent::Created
+            | gio::FileMonitorEvent::Deleted
+            | gio::FileMonitorEvent::Renamed
+            | gio::FileMonitorEvent::MovedIn
+            | gio::FileMonitorEvent::MovedOut
+    )
+}
+
+/// Monitor `file`, as a directory if `directory` is true, and send to `changes` on every relevant change.
+fn monitor_changes(
+    file: &gio::File,
+    directory: bool,
+    changes: futures_channel::mpsc::UnboundedSender<()>,
+) -> Result<gio::FileMonitor> {
+    let monitor = if directory {
+        file.monitor_directory(gio::FileMonitorFlags::NONE, gio::NONE_CANCELLABLE)
+    } else {
+        file.monitor_file(gio::FileMonitorFlags::NONE, gio::NONE_CANCELLABLE)
+    }
+    .with_context(|| format!("Failed to monitor {}", file.uri()))?;
+    monitor.connect_changed(move |_, file, _, event| {
+        if is_relevant_change(event) {
+            trace!("{} changed: {:?}", file.uri(), event);
+            // The receiver is gone if nothing watches the source anymore
+            let _ = changes.unbounded_send(());
+        }
+    });
+    Ok(monitor)
+}
+
+/// Watch recent projects for `app_id` in `config`, and send to `changes` on every change.
+///
+/// Monitor the file with recent projects of the latest version, as well as the vendor directory,
+/// to move to the file of a new version once one gets installed.
+async fn watch_recent_projects(
+    config: &ConfigLocation<'_>,
+    app_id: AppId,
+    changes: futures_channel::mpsc::UnboundedSender<()>,
+) -> Result<()> {
+    let config_home = glib::user_config_dir();
+    let vendor_dir = gio::File::for_path(config_home.join(config.vendor_dir));
+    let (send, mut versions_changed) = futures_channel::mpsc::unbounded();
+    let _vendor_monitor = monitor_changes(&vendor_dir, true, send)?;
+    loop {
+        let _projects_monitor = match config.find_latest_recent_projects_file(&config_home).await {
+            Ok(path) => {
+                debug!("Watching {} for {}", path.display(), app_id);
+                Some(monitor_changes(
+                    &gio::File::for_path(path),
+                    false,
+                    changes.clone(),
+                )?)
+            }
+            Err(error) => {
+                debug!("Not watching recent projects of {}: {:#}", app_id, error);
+                None
+            }
+        };
+        if versions_changed.next().await.is_none() || changes.is_closed() {
+            return Ok(());
+        }
+        debug!("Configuration directories of {} changed", app_id);
+        let _ = changes.unbounded_send(());
+    }
 }
 
 #[async_trait]
@@ -396,9 +551,43 @@ impl AsyncItemsSource<AppLaunchItem> for JetbrainsProjectsSource<'static> {
 
     #[instrument()]
     async fn find_recent_items(&self) -> Result<IdMap<AppLaunchItem>, Self::Err> {
+        let index = self.index.lock().take();
+        if let Some(index) = index {
+            info!(
+                "Using {} project(s) from index for {}",
+                index.projects.len(),
+                self.app_id
+            );
+            let items = index.items(&self.app_id);
+            let app_id = self.app_id.clone();
+            let config = self.config;
+            let changes = self.index_changes.lock().clone();
+            // Validate the index in the background, and report a change if it's outdated
+            glib::MainContext::default().invoke(move || {
+                glib::MainContext::default().spawn_local(async move {
+                    match is_index_current(config, &index.stamp).await {
+                        Ok(true) => debug!("Index of {} is up to date", app_id),
+                        result => {
+                            if let Err(error) = result {
+                                debug!("Failed to validate index of {}: {:#}", app_id, error);
+                            }
+                            info!("Index of {} is outdated, reloading", app_id);
+                            if let Some(changes) = changes {
+                                let _ = changes.unbounded_send(());
+                            }
+                        }
+                    }
+                });
+            });
+            return Ok(items);
+        }
+
         let (send, recv) = futures_channel::oneshot::channel();
         let app_id = self.app_id.clone();
         let config = self.config;
+        let name_sources = self.name_sources.clone();
+        let index_path = self.index_path.clone();
+        let saved_index = self.saved_index.clone();
         let span = Span::current();
         // Move to the main thread and then asynchronously read recent items through Gio,
         // and get them sent back to us via a oneshot channel.  We can't run the future
@@ -406,19 +595,60 @@ impl AsyncItemsSource<AppLaunchItem> for JetbrainsProjectsSource<'static> {
         glib::MainContext::default().invoke(move || {
             glib::MainContext::default().spawn_local(
                 async move {
-                    let result = read_recent_items(config, app_id).await;
-                    send.send(result).unwrap();
+                    match read_recent_items(config, app_id.clone(), &name_sources).await {
+                        Ok(index) => {
+                            send.send(Ok(index.items(&app_id))).unwrap();
+                            // Compare the whole index, including its stamp, so that the saved
+                            // index stays valid after a change that left projects as they were.
+                            if saved_index.lock().as_ref() == Some(&index) {
+                                debug!("Index of {} unchanged, not saving", app_id);
+                            } else if let Err(error) = index.save(&index_path).await {
+                                warn!("Failed to save index of {}: {:#}", app_id, error);
+                            } else {
+                                *saved_index.lock() = Some(index);
+                            }
+                        }
+                        Err(error) => send.send(Err(error)).unwrap(),
+                    }
                 }
                 .instrument(span),
             );
         });
         recv.await.unwrap()
     }
+
+    fn watch(&self) -> Option<ItemsChanges> {
+        let (send, recv) = futures_channel::mpsc::unbounded();
+        *self.index_changes.lock() = Some(send.clone());
+        let app_id = self.app_id.clone();
+        let config = self.config;
+        // Gio file monitors aren't Send either, so move to the main thread to create them
+        glib::MainContext::default().invoke(move || {
+            glib::MainContext::default().spawn_local(async move {
+                if let Err(error) = watch_recent_projects(config, app_id.clone(), send).await {
+                    error!("Failed to watch recent projects of {}: {:#}", app_id, error);
+                }
+            });
+        });
+        Some(recv.boxed())
+    }
 }
 
 /// The name to request on the bus.
 const BUSNAME: &str = "de.swsnr.searchprovider.Jetbrains";
 
+/// The object path of the scopes of launched IDEs.
+const SCOPES_OBJPATH: &str = "/de/swsnr/searchprovider/jetbrains/scopes";
+
+/// How long shutdown waits for pending launches.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often to update the status of the service in systemd.
+const STATUS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The search provider for recent projects of a Jetbrains IDE.
+type JetbrainsSearchProvider = AppItemSearchProvider<JetbrainsProjectsSource<'static>>;
+
 async fn tick(connection: zbus::Connection) {
     loop {
         connection.executor().tick().await
@@ -432,6 +662,20 @@ struct Service {
     launch_service: AppLaunchService,
     /// The DBus connection of this service.
     connection: zbus::Connection,
+    /// The providers served on the connection.
+    providers: Vec<&'static ProviderDefinition<'static>>,
+    /// The history of activated projects, if it loaded.
+    history: Option<Arc<Mutex<ActivationHistory>>>,
+}
+
+/// Apply the search and launch settings in `config` to the search `provider` for `desktop_id`.
+fn configure_provider(provider: &mut JetbrainsSearchProvider, config: &Config, desktop_id: &str) {
+    let provider_config = config.provider_config(desktop_id);
+    provider.set_roots(config.roots.clone());
+    provider.set_load_timeout(config.load_timeout);
+    provider.set_max_results(provider_config.max_results);
+    provider.set_min_score(provider_config.min_score);
+    provider.set_launch_options(provider_config.launch.clone());
 }
 
 /// Starts the DBUS service.
@@ -441,7 +685,23 @@ struct Service {
 ///
 /// Then register the connection on the Glib main loop and install a callback to
 /// handle incoming messages.
-async fn start_dbus_service(log_control: LogControl) -> Result<Service> {
+async fn start_dbus_service(
+    config: &Config,
+    log_control: LogControl,
+    idle_tracker: IdleTracker,
+) -> Result<Service> {
+    let history =
+        match ActivationHistory::load(ActivationHistory::default_path(env!("CARGO_PKG_NAME"))) {
+            Ok(history) => Some(Arc::new(Mutex::new(history))),
+            Err(error) => {
+                error!(
+                    "Failed to load activation history, not ranking by activations: {:#}",
+                    error
+                );
+                None
+            }
+        };
+
     let launch_service = AppLaunchService::new();
     // Create search providers for all apps we find
     let providers = PROVIDERS
@@ -449,17 +709,34 @@ async fn start_dbus_service(log_control: LogControl) -> Result<Service> {
         .filter_map(|provider| {
             gio::DesktopAppInfo::new(provider.desktop_id).map(|app| {
                 info!("Found app {}", provider.desktop_id);
-                (
-                    provider.objpath(),
-                    AppItemSearchProvider::new(
-                        app.into(),
-                        JetbrainsProjectsSource {
-                            app_id: provider.desktop_id.into(),
-                            config: &provider.config,
-                        },
-                        launch_service.client(),
+                let mut search_provider: JetbrainsSearchProvider = AppItemSearchProvider::new(
+                    app.into(),
+                    JetbrainsProjectsSource::new(
+                        provider.desktop_id.into(),
+                        &provider.config,
+                        config.name_sources.clone(),
                     ),
-                )
+                    launch_service.client(),
+                );
+                let cli = config
+                    .provider_config(provider.desktop_id)
+                    .cli
+                    .clone()
+                    .unwrap_or_else(|| provider.cli.to_string());
+                search_provider = search_provider
+                    .with_idle_tracker(idle_tracker.clone())
+                    .with_launched_scopes(launch_service.scopes())
+                    .with_running_instance(JetbrainsInstance::new(
+                        provider.desktop_id.into(),
+                        &provider.config,
+                        cli,
+                    ));
+                if let Some(history) = &history {
+                    search_provider = search_provider.with_history(history.clone());
+                }
+                search_provider.start_watching(&glib::MainContext::default());
+                configure_provider(&mut search_provider, config, provider.desktop_id);
+                (provider, search_provider)
             })
         })
         .collect::<Vec<_>>();
@@ -469,11 +746,13 @@ async fn start_dbus_service(log_control: LogControl) -> Result<Service> {
         providers.len(),
         BUSNAME
     );
+    let served = providers.iter().map(|(provider, _)| *provider).collect();
     let connection = providers
         .into_iter()
         .try_fold(
             zbus::ConnectionBuilder::session()?,
-            |b, (path, provider)| {
+            |b, (definition, provider)| {
+                let path = definition.objpath();
                 debug!(
                     "Serving search provider for app {} at {}",
                     provider.app().id(),
@@ -483,6 +762,7 @@ async fn start_dbus_service(log_control: LogControl) -> Result<Service> {
             },
         )?
         .serve_at("/org/freedesktop/LogControl1", log_control)?
+        .serve_at(SCOPES_OBJPATH, launch_service.scopes())?
         .name(BUSNAME)?
         // We disable the internal executor because we'd like to run the connection
         // exclusively on the glib mainloop, and thus tick it manually (see below).
@@ -498,9 +778,113 @@ async fn start_dbus_service(log_control: LogControl) -> Result<Service> {
     Ok(Service {
         launch_service,
         connection,
+        providers: served,
+        history,
     })
 }
 
+/// Reload configuration and apply it to the search `providers` served on `connection`.
+///
+/// Only the settings which [`configure_provider`] applies take effect, i.e. search and launch
+/// settings; other settings, e.g. `cli` or resource controls of scopes, require a restart.
+async fn reload_config(
+    connection: &zbus::Connection,
+    providers: &[&ProviderDefinition<'_>],
+) -> Result<()> {
+    let config = Config::load_from(&Config::default_path())?;
+    debug!("Reloaded configuration {:?}", config);
+    for provider in providers {
+        let interface = connection
+            .object_server()
+            .interface::<_, JetbrainsSearchProvider>(provider.objpath())
+            .await
+            .with_context(|| format!("Failed to get search provider for {}", provider.label))?;
+        configure_provider(
+            &mut *interface.get_mut().await,
+            &config,
+            provider.desktop_id,
+        );
+    }
+    info!("Applied configuration to {} provider(s)", providers.len());
+    Ok(())
+}
+
+/// Describe the state of the search `providers` on `connection`, for `systemctl status`.
+async fn service_status(
+    connection: &zbus::Connection,
+    providers: &[&ProviderDefinition<'_>],
+) -> String {
+    let mut items = 0;
+    for provider in providers {
+        match connection
+            .object_server()
+            .interface::<_, JetbrainsSearchProvider>(provider.objpath())
+            .await
+        {
+            Ok(interface) => items += interface.get().await.item_count(),
+            Err(error) => warn!(
+                "Failed to get search provider for {}: {:#}",
+                provider.label, error
+            ),
+        }
+    }
+    format!(
+        "Serving {} search provider(s) with {} recent project(s)",
+        providers.len(),
+        items
+    )
+}
+
+/// Report the status of the search `providers` on `connection` to systemd, whenever it changes.
+async fn report_status(connection: zbus::Connection, providers: Vec<&ProviderDefinition<'_>>) {
+    let mut last_status = String::new();
+    loop {
+        let status = service_status(&connection, &providers).await;
+        if status != last_status {
+            debug!("Service status changed: {}", status);
+            notify_status(&status);
+            last_status = status;
+        }
+        glib::timeout_future(STATUS_INTERVAL).await;
+    }
+}
+
+/// Shut down the service on `connection`.
+///
+/// Remove all search `providers` from the bus and release the bus name, so that D-Bus starts a
+/// new instance for subsequent calls.  Then wait for `pending` launches, but not longer than
+/// [`SHUTDOWN_TIMEOUT`], to let launched apps move to their own scopes.
+///
+/// The activation history and the indexes of recent projects need no flushing, because the
+/// service writes them as soon as they change.
+async fn shutdown(
+    connection: &zbus::Connection,
+    providers: &[&ProviderDefinition<'_>],
+    pending: &PendingLaunches,
+) {
+    info!("Shutting down");
+    notify_stopping();
+    for provider in providers {
+        let path = provider.objpath();
+        let result = connection
+            .object_server()
+            .remove::<JetbrainsSearchProvider, _>(path.as_str())
+            .await;
+        if let Err(error) = result {
+            warn!("Failed to remove search provider at {}: {:#}", path, error);
+        }
+    }
+    match connection.release_name(BUSNAME).await {
+        Ok(_) => debug!("Released name {}", BUSNAME),
+        Err(error) => error!("Failed to release name {}: {:#}", BUSNAME, error),
+    }
+    if pending.wait(SHUTDOWN_TIMEOUT).await {
+        info!("All launches completed, exiting");
+    } else {
+        warn!("Exiting with {} launch(es) still pending", pending.count());
+    }
+}
+
 fn app() -> clap::App<'static> {
     use clap::*;
     app_from_crate!()
@@ -516,6 +900,62 @@ Set $RUST_LOG to control the log level",
                 .long("--providers")
                 .help("List all providers"),
         )
+        .arg(
+            Arg::new("history")
+                .long("--history")
+                .conflicts_with("providers")
+                .help("List activated search results"),
+        )
+        .arg(
+            Arg::new("clear-history")
+                .long("--clear-history")
+                .conflicts_with_all(&["providers", "history"])
+                .help("Forget all activated search results"),
+        )
+}
+
+/// Format `timestamp` in seconds since the epoch as local time.
+fn format_timestamp(timestamp: u64) -> String {
+    i64::try_from(timestamp)
+        .ok()
+        .and_then(|timestamp| glib::DateTime::from_unix_local(timestamp).ok())
+        .and_then(|datetime| datetime.format("%Y-%m-%d %H:%M").ok())
+        .map_or_else(|| timestamp.to_string(), |s| s.to_string())
+}
+
+/// Print all activations in the history at `path`.
+fn print_history(path: PathBuf) -> Result<()> {
+    let history = ActivationHistory::load(path.clone())
+        .with_context(|| format!("Failed to load history from {}", path.display()))?;
+    for activation in history.activations() {
+        let item = match activation.id.parse::<AppItemId>() {
+            Ok(id) => format!("{} {}", id.app(), id.uri()),
+            Err(_) => activation.id.clone(),
+        };
+        println!(
+            "{}\t{}\t{}",
+            format_timestamp(activation.timestamp),
+            item,
+            activation.terms.join(" ")
+        );
+    }
+    Ok(())
+}
+
+/// Clear the history at `path`.
+///
+/// A running service keeps the activations it loaded until it reloads.
+fn clear_history(path: PathBuf) -> Result<()> {
+    let mut history = ActivationHistory::load(path.clone())
+        .with_context(|| format!("Failed to load history from {}", path.display()))?;
+    history
+        .clear()
+        .with_context(|| format!("Failed to clear history at {}", path.display()))?;
+    println!(
+        "Cleared history; reload the service to apply, e.g. with systemctl --user reload {}.service",
+        env!("CARGO_PKG_NAME")
+    );
+    Ok(())
 }
 
 fn main() {
@@ -526,6 +966,17 @@ fn main() {
         for label in labels {
             println!("{}", label)
         }
+    } else if matches.is_present("history") || matches.is_present("clear-history") {
+        let path = ActivationHistory::default_path(env!("CARGO_PKG_NAME"));
+        let result = if matches.is_present("history") {
+            print_history(path)
+        } else {
+            clear_history(path)
+        };
+        if let Err(error) = result {
+            eprintln!("{:#}", error);
+            std::process::exit(1);
+        }
     } else {
         let log_control = setup_logging_for_service();
 
@@ -535,22 +986,102 @@ fn main() {
             env!("CARGO_PKG_VERSION")
         );
 
+        let config = Config::load_from(&Config::default_path()).unwrap_or_else(|error| {
+            error!("Failed to load configuration, using defaults: {:#}", error);
+            Config::default()
+        });
+        debug!("Using configuration {:?}", config);
+
         trace!("Acquire main context");
         let context = glib::MainContext::default();
         context.push_thread_default();
 
-        match context.block_on(start_dbus_service(log_control)) {
+        let idle_tracker = IdleTracker::new();
+        match context.block_on(start_dbus_service(
+            &config,
+            log_control,
+            idle_tracker.clone(),
+        )) {
             Ok(service) => {
+                let connection = service.connection.clone();
+                let providers = service.providers;
+                let pending = service.launch_service.pending();
+                let scopes = service.launch_service.scopes();
                 let _ = service.launch_service.start(
                     &context,
                     service.connection,
                     SystemdScopeSettings {
+                        mode: config.scope_mode,
                         prefix: concat!("app-", env!("CARGO_BIN_NAME")).to_string(),
                         started_by: env!("CARGO_BIN_NAME").to_string(),
                         documentation: vec![env!("CARGO_PKG_HOMEPAGE").to_string()],
+                        resources: config.provider.scope.clone(),
+                        app_resources: config
+                            .providers
+                            .iter()
+                            .map(|(id, provider)| (id.clone(), provider.scope.clone()))
+                            .collect(),
                     },
                 );
-                create_main_loop(&context).run();
+                let reload_connection = connection.clone();
+                let reload_providers = providers.clone();
+                let history = service.history;
+                context.spawn_local(async move {
+                    let mut hangups = hangup_signals();
+                    while hangups.next().await.is_some() {
+                        info!("Received SIGHUP, reloading configuration");
+                        notify_reloading();
+                        if let Err(error) =
+                            reload_config(&reload_connection, &reload_providers).await
+                        {
+                            error!("Failed to reload configuration: {:#}", error);
+                        }
+                        // Pick up changes from --clear-history
+                        if let Some(history) = &history {
+                            if let Err(error) = history.lock().reload() {
+                                error!("Failed to reload activation history: {:#}", error);
+                            }
+                        }
+                        notify_ready(&service_status(&reload_connection, &reload_providers).await);
+                    }
+                });
+
+                notify_ready(&format!("Serving {} search provider(s)", providers.len()));
+                context.spawn_local(keep_watchdog_alive());
+                context.spawn_local(report_status(connection.clone(), providers.clone()));
+
+                let mainloop = glib::MainLoop::new(Some(&context), false);
+                let idle_timeout = config.idle_timeout;
+                let quit_mainloop = mainloop.clone();
+                context.spawn_local(async move {
+                    let idle = async move {
+                        match idle_timeout {
+                            // Scopes of launched IDEs only live in memory, so stay alive while
+                            // IDEs still run in their scopes, to not forget about them.
+                            Some(timeout) => loop {
+                                wait_until_idle(&idle_tracker, timeout).await;
+                                if scopes.any_running() {
+                                    debug!("No calls for {:?}, but IDEs still run", timeout);
+                                    idle_tracker.touch();
+                                } else {
+                                    info!("No calls for {:?}", timeout);
+                                    break;
+                                }
+                            },
+                            None => std::future::pending().await,
+                        }
+                    };
+                    select(Box::pin(wait_for_termination()), Box::pin(idle)).await;
+                    // Quit right away if another signal arrives while shutting down
+                    let graceful = shutdown(&connection, &providers, &pending);
+                    if let Either::Right(_) =
+                        select(Box::pin(graceful), Box::pin(wait_for_termination())).await
+                    {
+                        warn!("Terminated during shutdown, exiting right away");
+                    }
+                    quit_mainloop.quit();
+                });
+                mainloop.run();
             }
             Err(error) => {
                 error!("Failed to start DBus server: {:#}", error);
@@ -604,6 +1135,21 @@ mod tests {
         )
     }
 
+    #[test]
+    fn read_open_projects() {
+        let data: &[u8] = include_bytes!("tests/recentProjects.xml");
+        let home = glib::home_dir();
+        assert_eq!(
+            parse_open_jetbrains_projects(data).unwrap(),
+            vec![home
+                .join("Code")
+                .join("gh")
+                .join("gnome-search-providers-jetbrains")
+                .to_string_lossy()
+                .to_string()]
+        )
+    }
+
     #[test]
     fn read_recent_solutions() {
         let data: &[u8] = include_bytes!("tests/recentSolutions.xml");
@@ -0,0 +1,664 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Information about projects on disk.
+
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use elementtree::Element;
+use lazy_static::lazy_static;
+use regex::Regex;
+use tracing::{debug, trace};
+
+use gnome_search_provider_common::futures_util::stream::{self, StreamExt};
+use gnome_search_provider_common::gio;
+use gnome_search_provider_common::gio::glib;
+use gnome_search_provider_common::gio::prelude::*;
+
+/// A source for the name of a project.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NameSource {
+    /// The `.idea/.name` file Jetbrains IDEs write for projects with a custom name.
+    Idea,
+    /// `rootProject.name` in `settings.gradle` or `settings.gradle.kts`.
+    Gradle,
+    /// The `artifactId` of the project in `pom.xml`.
+    Maven,
+    /// `package.name` in `Cargo.toml`.
+    Cargo,
+    /// The `name` in `package.json`.
+    Npm,
+    /// `project.name` or `tool.poetry.name` in `pyproject.toml`.
+    Pyproject,
+    /// The name of the project directory.
+    Directory,
+}
+
+/// The default order of name sources.
+pub const DEFAULT_NAME_SOURCES: &[NameSource] = &[
+    NameSource::Idea,
+    NameSource::Gradle,
+    NameSource::Maven,
+    NameSource::Cargo,
+    NameSource::Npm,
+    NameSource::Pyproject,
+    NameSource::Directory,
+];
+
+impl Display for NameSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            NameSource::Idea => "idea",
+            NameSource::Gradle => "gradle",
+            NameSource::Maven => "maven",
+            NameSource::Cargo => "cargo",
+            NameSource::Npm => "npm",
+            NameSource::Pyproject => "pyproject",
+            NameSource::Directory => "directory",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for NameSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "idea" => Ok(NameSource::Idea),
+            "gradle" => Ok(NameSource::Gradle),
+            "maven" => Ok(NameSource::Maven),
+            "cargo" => Ok(NameSource::Cargo),
+            "npm" => Ok(NameSource::Npm),
+            "pyproject" => Ok(NameSource::Pyproject),
+            "directory" => Ok(NameSource::Directory),
+            _ => Err(anyhow!("Unknown name source: {}", s)),
+        }
+    }
+}
+
+/// Read the contents of the file at `path` as string.
+async fn read_file(path: &Path) -> Result<String> {
+    let file = gio::File::for_path(path);
+    trace!("Reading {}", file.uri());
+    let (data, _) = file
+        .load_contents_async_future()
+        .await
+        .with_context(|| format!("Failed to read {}", file.uri()))?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// Read the first of `filenames` in `directory` which exists.
+async fn read_first_file(directory: &Path, filenames: &[&str]) -> Result<String> {
+    let mut errors = Vec::with_capacity(filenames.len());
+    for filename in filenames {
+        match read_file(&directory.join(filename)).await {
+            Ok(contents) => return Ok(contents),
+            Err(error) => errors.push(format!("{:#}", error)),
+        }
+    }
+    Err(anyhow!("{}", errors.join("; ")))
+}
+
+/// Extract `rootProject.name` from Gradle settings.
+fn parse_gradle_name(settings: &str) -> Option<String> {
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r#"(?m)^\s*rootProject\.name\s*=\s*["']([^"']+)["']"#).unwrap();
+    }
+    RE.captures(settings).map(|m| m[1].to_string())
+}
+
+/// Extract the `artifactId` of the project from a Maven POM.
+fn parse_maven_name(pom: &str) -> Result<Option<String>> {
+    let project = Element::from_reader(pom.as_bytes())?;
+    Ok(project
+        .children()
+        .find(|child| child.tag().name() == "artifactId")
+        .map(|artifact_id| artifact_id.text().trim().to_string()))
+}
+
+/// Extract `package.name` from `Cargo.toml`.
+fn parse_cargo_name(manifest: &str) -> Result<Option<String>> {
+    let manifest: toml::Value = manifest.parse()?;
+    Ok(manifest
+        .get("package")
+        .and_then(|package| package.get("name"))
+        .and_then(toml::Value::as_str)
+        .map(ToString::to_string))
+}
+
+/// Extract the `name` from `package.json`.
+fn parse_npm_name(package: &str) -> Result<Option<String>> {
+    let package: serde_json::Value = serde_json::from_str(package)?;
+    Ok(package
+        .get("name")
+        .and_then(serde_json::Value::as_str)
+        .map(ToString::to_string))
+}
+
+/// Extract `project.name` or `tool.poetry.name` from `pyproject.toml`.
+fn parse_pyproject_name(pyproject: &str) -> Result<Option<String>> {
+    let pyproject: toml::Value = pyproject.parse()?;
+    Ok(pyproject
+        .get("project")
+        .and_then(|project| project.get("name"))
+        .or_else(|| {
+            pyproject
+                .get("tool")
+                .and_then(|tool| tool.get("poetry"))
+                .and_then(|poetry| poetry.get("name"))
+        })
+        .and_then(toml::Value::as_str)
+        .map(ToString::to_string))
+}
+
+/// Read the name of the project at `path` from the given name `source`.
+///
+/// Return `None` if `source` exists but doesn't define a name.
+async fn read_name(source: NameSource, path: &Path) -> Result<Option<String>> {
+    match source {
+        NameSource::Idea => {
+            let name = read_file(&path.join(".idea").join(".name")).await?;
+            Ok(Some(name.trim().to_string()))
+        }
+        NameSource::Gradle => {
+            let settings =
+                read_first_file(path, &["settings.gradle.kts", "settings.gradle"]).await?;
+            Ok(parse_gradle_name(&settings))
+        }
+        NameSource::Maven => parse_maven_name(&read_file(&path.join("pom.xml")).await?),
+        NameSource::Cargo => parse_cargo_name(&read_file(&path.join("Cargo.toml")).await?),
+        NameSource::Npm => parse_npm_name(&read_file(&path.join("package.json")).await?),
+        NameSource::Pyproject => {
+            parse_pyproject_name(&read_file(&path.join("pyproject.toml")).await?)
+        }
+        NameSource::Directory => Ok(path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())),
+    }
+}
+
+/// Get the name of the project at the given path.
+///
+/// Try all `sources` in order, and return the first non-empty name.  Return `None` if no
+/// source provides a name.
+pub async fn get_project_name<P: AsRef<Path>>(path: P, sources: &[NameSource]) -> Option<String> {
+    let path = path.as_ref();
+    for source in sources {
+        match read_name(*source, path).await {
+            Ok(Some(name)) if !name.is_empty() => {
+                trace!("Found name {} for {} in {}", name, path.display(), source);
+                return Some(name);
+            }
+            Ok(_) => {
+                trace!("No name for {} in {}", path.display(), source);
+            }
+            Err(error) => {
+                debug!(
+                    "Failed to read name for {} from {}: {:#}",
+                    path.display(),
+                    source,
+                    error
+                );
+            }
+        }
+    }
+    None
+}
+
+/// How many projects to read concurrently.
+const MAX_CONCURRENT_LOOKUPS: usize = 16;
+
+/// Run `lookup` for all `paths`, with at most [`MAX_CONCURRENT_LOOKUPS`] pending lookups at a time.
+///
+/// Return the results of `lookup` in the order of `paths`.
+async fn lookup_concurrently<F, R, T>(paths: Vec<String>, lookup: F) -> Vec<T>
+where
+    F: FnMut(String) -> R,
+    R: Future<Output = T>,
+{
+    stream::iter(paths)
+        .map(lookup)
+        .buffered(MAX_CONCURRENT_LOOKUPS)
+        .collect()
+        .await
+}
+
+/// Get the names of all projects at the given `paths`.
+///
+/// Read names concurrently from `sources`, with at most [`MAX_CONCURRENT_LOOKUPS`] pending
+/// reads at a time.  Return pairs of path and name, in the order of `paths`.
+pub async fn get_project_names(
+    paths: Vec<String>,
+    sources: &[NameSource],
+) -> Vec<(String, Option<String>)> {
+    lookup_concurrently(paths, |path| async move {
+        let name = get_project_name(&path, sources).await;
+        (path, name)
+    })
+    .await
+}
+
+/// Files which indicate build systems and languages of a project, with the corresponding keywords.
+const PROJECT_MARKERS: &[(&str, &[&str])] = &[
+    ("build.gradle.kts", &["gradle", "kotlin"]),
+    ("settings.gradle.kts", &["gradle", "kotlin"]),
+    ("build.gradle", &["gradle", "java"]),
+    ("settings.gradle", &["gradle", "java"]),
+    ("pom.xml", &["maven", "java"]),
+    ("Cargo.toml", &["cargo", "rust"]),
+    ("package.json", &["npm", "javascript"]),
+    ("tsconfig.json", &["typescript"]),
+    ("pyproject.toml", &["python"]),
+    ("setup.py", &["python"]),
+    ("go.mod", &["go"]),
+];
+
+/// List the names of all files in `directory`.
+async fn list_directory(directory: &Path) -> Result<Vec<String>> {
+    let directory = gio::File::for_path(directory);
+    let files = directory
+        .enumerate_children_async_future(
+            &gio::FILE_ATTRIBUTE_STANDARD_NAME,
+            gio::FileQueryInfoFlags::NONE,
+            glib::PRIORITY_DEFAULT,
+        )
+        .await
+        .with_context(|| format!("Failed to enumerate children of {}", directory.uri()))?
+        .next_files_async_future(i32::MAX, glib::PRIORITY_DEFAULT)
+        .await
+        .with_context(|| format!("Failed to get children of {}", directory.uri()))?;
+    Ok(files
+        .iter()
+        .map(|f| f.name().to_string_lossy().to_string())
+        .collect())
+}
+
+/// Extract the URLs of all remotes from a git configuration file.
+fn parse_git_remote_urls(config: &str) -> Vec<String> {
+    let mut urls = Vec::new();
+    let mut in_remote = false;
+    for line in config.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_remote = line.starts_with("[remote ");
+        } else if in_remote {
+            if let Some((key, value)) = line.split_once('=') {
+                if key.trim() == "url" {
+                    urls.push(value.trim().to_string());
+                }
+            }
+        }
+    }
+    urls
+}
+
+/// Extract the repository, i.e. the path without host, port and `.git` suffix, from the `url`
+/// of a git remote.
+///
+/// Understands URLs with schemes like `https://github.com/acme/foo.git` as well as SCP-like
+/// SSH remotes like `git@github.com:acme/foo.git`, and returns `acme/foo` for both.  Return
+/// `None` for local repositories, and for repositories without an organisation.
+fn git_remote_repository(url: &str) -> Option<String> {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    if without_scheme.starts_with('/') {
+        // A local repository
+        return None;
+    }
+    let without_user = without_scheme
+        .split_once('@')
+        .map_or(without_scheme, |(_, rest)| rest);
+    let (_, path) = without_user.split_once(|c| c == '/' || c == ':')?;
+    let segments: Vec<&str> = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        // Skip over a port number, as in ssh://git@example.com:22/acme/foo
+        .skip_while(|s| s.chars().all(|c| c.is_ascii_digit()))
+        .collect();
+    // A single segment denotes the repository, not an organisation
+    if segments.len() < 2 {
+        return None;
+    }
+    let repository = segments.join("/");
+    Some(
+        repository
+            .strip_suffix(".git")
+            .unwrap_or(&repository)
+            .to_string(),
+    )
+}
+
+/// Detect keywords for the project at `path`.
+///
+/// Keywords include the build system and the main languages guessed from files in the project
+/// directory, and the organisations and repositories of all git remotes of the project, e.g.
+/// `acme` and `acme/service`.
+pub async fn detect_keywords<P: AsRef<Path>>(path: P) -> Vec<String> {
+    let path = path.as_ref();
+    let mut keywords = Vec::new();
+    match list_directory(path).await {
+        Ok(filenames) => {
+            for (marker, marker_keywords) in PROJECT_MARKERS {
+                if filenames.iter().any(|filename| filename == marker) {
+                    keywords.extend(marker_keywords.iter().map(ToString::to_string));
+                }
+            }
+        }
+        Err(error) => {
+            debug!("Failed to list files of {}: {:#}", path.display(), error);
+        }
+    }
+    match read_file(&path.join(".git").join("config")).await {
+        Ok(config) => {
+            for url in parse_git_remote_urls(&config) {
+                if let Some(repository) = git_remote_repository(&url) {
+                    if let Some((organisation, _)) = repository.split_once('/') {
+                        keywords.push(organisation.to_string());
+                    }
+                    keywords.push(repository);
+                }
+            }
+        }
+        Err(error) => {
+            trace!("No git configuration for {}: {:#}", path.display(), error);
+        }
+    }
+    let mut seen = HashSet::new();
+    keywords.retain(|keyword| seen.insert(keyword.clone()));
+    trace!("Detected keywords {:?} for {}", keywords, path.display());
+    keywords
+}
+
+/// Detect keywords for all projects at the given `paths`.
+///
+/// Like [`get_project_names`] look at projects concurrently, and return keywords in the order of `paths`.
+pub async fn detect_all_keywords(paths: &[String]) -> Vec<Vec<String>> {
+    stream::iter(paths)
+        .map(detect_keywords)
+        .buffered(MAX_CONCURRENT_LOOKUPS)
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::{Duration, Instant};
+
+    use pretty_assertions::assert_eq;
+
+    use gnome_search_provider_common::testutil::{block_on, TempDir};
+
+    use super::*;
+
+    #[test]
+    fn parse_name_sources() {
+        for source in DEFAULT_NAME_SOURCES {
+            assert_eq!(source.to_string().parse::<NameSource>().unwrap(), *source);
+        }
+        assert!("ant".parse::<NameSource>().is_err());
+    }
+
+    #[test]
+    fn gradle_name() {
+        let settings = r#"
+pluginManagement {
+    repositories { gradlePluginPortal() }
+}
+rootProject.name = "acme-service"
+include("core")
+"#;
+        assert_eq!(
+            parse_gradle_name(settings),
+            Some("acme-service".to_string())
+        );
+        assert_eq!(
+            parse_gradle_name("rootProject.name = 'groovy-style'"),
+            Some("groovy-style".to_string())
+        );
+        assert_eq!(parse_gradle_name("include 'core'"), None);
+    }
+
+    #[test]
+    fn maven_name() {
+        let pom = r#"<?xml version="1.0" encoding="UTF-8"?>
+<project xmlns="http://maven.apache.org/POM/4.0.0">
+  <modelVersion>4.0.0</modelVersion>
+  <parent>
+    <artifactId>acme-parent</artifactId>
+  </parent>
+  <artifactId>acme-service</artifactId>
+</project>"#;
+        assert_eq!(
+            parse_maven_name(pom).unwrap(),
+            Some("acme-service".to_string())
+        );
+    }
+
+    #[test]
+    fn cargo_name() {
+        let manifest = "[package]\nname = \"mdcat\"\nversion = \"1.0.0\"\n";
+        assert_eq!(
+            parse_cargo_name(manifest).unwrap(),
+            Some("mdcat".to_string())
+        );
+        assert_eq!(
+            parse_cargo_name("[workspace]\nmembers = []\n").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn npm_name() {
+        let package = r#"{"name": "@acme/frontend", "dependencies": {"name": "foo"}}"#;
+        assert_eq!(
+            parse_npm_name(package).unwrap(),
+            Some("@acme/frontend".to_string())
+        );
+    }
+
+    #[test]
+    fn pyproject_name() {
+        assert_eq!(
+            parse_pyproject_name("[project]\nname = \"acme-tools\"\n").unwrap(),
+            Some("acme-tools".to_string())
+        );
+        assert_eq!(
+            parse_pyproject_name("[tool.poetry]\nname = \"acme-poetry\"\n").unwrap(),
+            Some("acme-poetry".to_string())
+        );
+    }
+
+    #[test]
+    fn name_from_first_matching_source() {
+        let dir = TempDir::new();
+        let project = dir.create_dir(
+            "service",
+            &[
+                (
+                    "settings.gradle.kts",
+                    "rootProject.name = \"from-gradle\"\n",
+                ),
+                ("package.json", r#"{"name": "from-npm"}"#),
+            ],
+        );
+        assert_eq!(
+            block_on(get_project_name(&project, DEFAULT_NAME_SOURCES)),
+            Some("from-gradle".to_string())
+        );
+        assert_eq!(
+            block_on(get_project_name(
+                &project,
+                &[NameSource::Npm, NameSource::Gradle]
+            )),
+            Some("from-npm".to_string())
+        );
+        assert_eq!(
+            block_on(get_project_name(
+                &project,
+                &[NameSource::Cargo, NameSource::Directory]
+            )),
+            Some("service".to_string())
+        );
+        assert_eq!(
+            block_on(get_project_name(&project, &[NameSource::Cargo])),
+            None
+        );
+    }
+
+    #[test]
+    fn idea_name_takes_precedence_by_default() {
+        let dir = TempDir::new();
+        let project = dir.create_dir(
+            "service",
+            &[
+                (".idea/.name", "From IDEA\n"),
+                ("Cargo.toml", "[package]\nname = \"from-cargo\"\n"),
+            ],
+        );
+        assert_eq!(
+            block_on(get_project_name(&project, DEFAULT_NAME_SOURCES)),
+            Some("From IDEA".to_string())
+        );
+    }
+
+    #[test]
+    fn git_remote_urls() {
+        let config = r#"[core]
+	repositoryformatversion = 0
+	bare = false
+[remote "origin"]
+	url = git@github.com:acme/service.git
+	fetch = +refs/heads/*:refs/remotes/origin/*
+[branch "main"]
+	remote = origin
+[remote "upstream"]
+	url = https://gitlab.example.com/initech/service.git
+"#;
+        assert_eq!(
+            parse_git_remote_urls(config),
+            vec![
+                "git@github.com:acme/service.git",
+                "https://gitlab.example.com/initech/service.git"
+            ]
+        );
+    }
+
+    #[test]
+    fn git_remote_repositories() {
+        assert_eq!(
+            git_remote_repository("git@github.com:acme/service.git"),
+            Some("acme/service".to_string())
+        );
+        assert_eq!(
+            git_remote_repository("https://github.com/acme/service.git"),
+            Some("acme/service".to_string())
+        );
+        assert_eq!(
+            git_remote_repository("ssh://git@example.com:2222/acme/service.git"),
+            Some("acme/service".to_string())
+        );
+        assert_eq!(
+            git_remote_repository("https://gitlab.com/acme/backend/service"),
+            Some("acme/backend/service".to_string())
+        );
+        assert_eq!(git_remote_repository("/srv/git/service.git"), None);
+        assert_eq!(git_remote_repository("git@example.com:service.git"), None);
+    }
+
+    #[test]
+    fn keywords_of_project() {
+        let dir = TempDir::new();
+        let project = dir.create_dir(
+            "service",
+            &[
+                ("settings.gradle.kts", "rootProject.name = \"service\"\n"),
+                ("build.gradle.kts", ""),
+                (
+                    ".git/config",
+                    "[remote \"origin\"]\n\turl = git@github.com:acme/service.git\n",
+                ),
+            ],
+        );
+        assert_eq!(
+            block_on(detect_keywords(&project)),
+            vec!["gradle", "kotlin", "acme", "acme/service"]
+        );
+    }
+
+    /// Create `count` projects; every other project has a name file.
+    fn many_projects(dir: &TempDir, count: usize) -> Vec<(String, Option<String>)> {
+        (0..count)
+            .map(|i| {
+                let directory_name = format!("project-{}", i);
+                if i % 2 == 0 {
+                    let name = format!("Project {}", i);
+                    let path = dir.create_dir(&directory_name, &[(".idea/.name", &name)]);
+                    (path.to_string_lossy().to_string(), Some(name))
+                } else {
+                    let path = dir.create_dir(&directory_name, &[]);
+                    (path.to_string_lossy().to_string(), Some(directory_name))
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn names_in_order_of_paths() {
+        let dir = TempDir::new();
+        let expected = many_projects(&dir, 100);
+        let paths = expected.iter().map(|(path, _)| path.clone()).collect();
+        let names = block_on(get_project_names(paths, DEFAULT_NAME_SOURCES));
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn slow_lookups_run_concurrently() {
+        let count = 64;
+        let paths = (0..count)
+            .map(|i| format!("/projects/{}", i))
+            .collect::<Vec<_>>();
+        // Later lookups of every batch finish first, so that the order of completion differs
+        // from the order of paths.
+        let delay = |i: usize| Duration::from_millis(5 * (16 - i as u64 % 16));
+        let serial_time = (0..count).map(delay).sum::<Duration>();
+        let pending = Cell::new(0);
+        let max_pending = Cell::new(0);
+
+        let start = Instant::now();
+        let names = block_on(lookup_concurrently(paths.clone(), |path| {
+            let (pending, max_pending) = (&pending, &max_pending);
+            async move {
+                pending.set(pending.get() + 1);
+                max_pending.set(max_pending.get().max(pending.get()));
+                let i: usize = path.rsplit('/').next().unwrap().parse().unwrap();
+                glib::timeout_future(delay(i)).await;
+                pending.set(pending.get() - 1);
+                (path, format!("Project {}", i))
+            }
+        }));
+        let elapsed = start.elapsed();
+
+        assert_eq!(max_pending.get(), MAX_CONCURRENT_LOOKUPS);
+        assert!(
+            elapsed < serial_time / 4,
+            "Lookups took {:?}, serial lookups take {:?}",
+            elapsed,
+            serial_time
+        );
+        let expected = paths
+            .iter()
+            .enumerate()
+            .map(|(i, path)| (path.clone(), format!("Project {}", i)))
+            .collect::<Vec<_>>();
+        assert_eq!(names, expected);
+    }
+}
@@ -0,0 +1,415 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A harness to test the service end to end over D-Bus.
+//!
+//! The harness runs a private `dbus-daemon`, a stub systemd manager on that bus, and the service
+//! itself, with `$HOME` and all XDG directories in a temporary directory.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::net::TcpListener;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use gnome_search_provider_common::glib;
+use gnome_search_provider_common::parking_lot::Mutex;
+use gnome_search_provider_common::zbus;
+use gnome_search_provider_common::zbus::dbus_interface;
+use gnome_search_provider_common::zbus::zvariant::{OwnedObjectPath, OwnedValue};
+
+/// The bus name of the service.
+pub const BUSNAME: &str = "de.swsnr.searchprovider.Jetbrains";
+
+/// The interface of search providers.
+pub const SEARCH_PROVIDER_INTERFACE: &str = "org.gnome.Shell.SearchProvider2";
+
+/// How long to wait for the service.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Configuration for the private bus.
+const BUS_CONFIG: &str = r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <type>session</type>
+  <listen>unix:tmpdir=/tmp</listen>
+  <auth>EXTERNAL</auth>
+  <policy context="default">
+    <allow send_destination="*" eavesdrop="true"/>
+    <allow eavesdrop="true"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>
+"#;
+
+/// A unit started by the stub systemd manager.
+#[derive(Debug, Clone)]
+pub struct StartedUnit {
+    /// The name of the unit.
+    pub name: String,
+    /// The properties of the unit.
+    pub properties: HashMap<String, OwnedValue>,
+}
+
+/// A stub systemd manager which records started units.
+struct StubManager {
+    started: Arc<Mutex<Vec<StartedUnit>>>,
+}
+
+#[dbus_interface(name = "org.freedesktop.systemd1.Manager")]
+impl StubManager {
+    fn start_transient_unit(
+        &self,
+        name: String,
+        _mode: String,
+        properties: Vec<(String, OwnedValue)>,
+        _aux: Vec<(String, Vec<(String, OwnedValue)>)>,
+    ) -> OwnedObjectPath {
+        self.started.lock().push(StartedUnit {
+            name,
+            properties: properties.into_iter().collect(),
+        });
+        OwnedObjectPath::try_from(STUB_UNIT_PATH).unwrap()
+    }
+}
+
+/// The object path of all units started by the stub systemd manager.
+const STUB_UNIT_PATH: &str = "/org/freedesktop/systemd1/unit/stub";
+
+/// A stub systemd unit which is always active.
+struct StubUnit;
+
+#[dbus_interface(name = "org.freedesktop.systemd1.Unit")]
+impl StubUnit {
+    #[dbus_interface(property)]
+    fn active_state(&self) -> String {
+        "active".to_string()
+    }
+}
+
+/// Wait until `condition` holds, but not longer than [`TIMEOUT`].
+///
+/// Return whether `condition` holds eventually.
+pub fn wait_for<F: FnMut() -> bool>(mut condition: F) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < TIMEOUT {
+        if condition() {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    condition()
+}
+
+/// Write `contents` to `path`, and create all parent directories.
+fn write_file<C: AsRef<[u8]>>(path: &Path, contents: C) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, contents).unwrap();
+}
+
+/// A running service on a private bus.
+pub struct Harness {
+    /// The temporary directory of this harness.
+    root: PathBuf,
+    /// The address of the private bus.
+    address: String,
+    /// The private bus.
+    dbus_daemon: Child,
+    /// The service, once started.
+    service: Option<Child>,
+    /// The connection of the stub systemd manager.
+    _systemd: zbus::blocking::Connection,
+    /// Units started by the stub systemd manager.
+    started_units: Arc<Mutex<Vec<StartedUnit>>>,
+    /// Sockets of fake running IDEs.
+    listeners: Vec<TcpListener>,
+    /// A connection to the private bus, to talk to the service.
+    pub connection: zbus::blocking::Connection,
+}
+
+impl Harness {
+    /// Start a private bus with a stub systemd manager.
+    ///
+    /// Panic if `dbus-daemon` is not available.
+    pub fn new() -> Self {
+        if let Err(error) = Command::new("dbus-daemon")
+            .arg("--version")
+            .stdout(Stdio::null())
+            .status()
+        {
+            panic!(
+                "dbus-daemon not available, can't run end to end tests: {}",
+                error
+            );
+        }
+
+        let root = glib::mkdtemp(
+            std::env::temp_dir().join("gnome-search-providers-jetbrains-harness-XXXXXX"),
+        )
+        .unwrap();
+        let bus_config = root.join("bus.conf");
+        write_file(&bus_config, BUS_CONFIG);
+        let mut dbus_daemon = Command::new("dbus-daemon")
+            .arg(format!("--config-file={}", bus_config.display()))
+            .arg("--nofork")
+            .arg("--print-address")
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut address = String::new();
+        BufReader::new(dbus_daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        let address = address.trim().to_string();
+
+        let started_units = Arc::new(Mutex::new(Vec::new()));
+        let systemd = zbus::blocking::ConnectionBuilder::address(address.as_str())
+            .unwrap()
+            .name("org.freedesktop.systemd1")
+            .unwrap()
+            .serve_at(
+                "/org/freedesktop/systemd1",
+                StubManager {
+                    started: started_units.clone(),
+                },
+            )
+            .unwrap()
+            .serve_at(STUB_UNIT_PATH, StubUnit)
+            .unwrap()
+            .build()
+            .unwrap();
+        let connection = zbus::blocking::ConnectionBuilder::address(address.as_str())
+            .unwrap()
+            .build()
+            .unwrap();
+
+        Self {
+            root,
+            address,
+            dbus_daemon,
+            service: None,
+            _systemd: systemd,
+            started_units,
+            listeners: Vec::new(),
+            connection,
+        }
+    }
+
+    /// The home directory of the service.
+    pub fn home(&self) -> PathBuf {
+        self.root.join("home")
+    }
+
+    /// Install a fake app with the given `desktop_id` and `name`.
+    ///
+    /// The app appends its arguments, i.e. the URI it gets launched with, to the file at
+    /// [`Harness::launched_file`], prefixed with `$HARNESS_LAUNCH_PREFIX` if set.
+    pub fn add_app(&self, desktop_id: &str, name: &str) {
+        let executable = self.root.join("bin").join(desktop_id);
+        write_file(
+            &executable,
+            format!(
+                "#!/bin/sh\necho \"${{HARNESS_LAUNCH_PREFIX}}$*\" >> '{}'\n",
+                self.launched_file().display()
+            ),
+        );
+        std::fs::set_permissions(&executable, std::fs::Permissions::from_mode(0o755)).unwrap();
+        write_file(
+            &self.root.join("data").join("applications").join(desktop_id),
+            format!(
+                "[Desktop Entry]\nType=Application\nName={}\nIcon=text-x-generic\nExec={} %u\n",
+                name,
+                executable.display()
+            ),
+        );
+    }
+
+    /// Install a fake command line launcher of an IDE, and return its path.
+    ///
+    /// The launcher appends every URI it gets with a `cli:` prefix to the file at
+    /// [`Harness::launched_file`].
+    pub fn add_cli(&self, name: &str) -> PathBuf {
+        let executable = self.root.join("bin").join(name);
+        write_file(
+            &executable,
+            format!(
+                "#!/bin/sh\necho \"cli:$1\" >> '{}'\n",
+                self.launched_file().display()
+            ),
+        );
+        std::fs::set_permissions(&executable, std::fs::Permissions::from_mode(0o755)).unwrap();
+        executable
+    }
+
+    /// Pretend that an IDE with the configuration directory `config_dir` runs.
+    ///
+    /// Listen on a port and write the port to the `.port` file in `config_dir`, which is relative
+    /// to `$XDG_CONFIG_HOME`.
+    pub fn run_ide(&mut self, config_dir: &str) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        write_file(
+            &self.root.join("config").join(config_dir).join(".port"),
+            listener.local_addr().unwrap().port().to_string(),
+        );
+        self.listeners.push(listener);
+    }
+
+    /// The file fake apps record their launches in.
+    pub fn launched_file(&self) -> PathBuf {
+        self.root.join("launched")
+    }
+
+    /// All URIs fake apps were launched with so far.
+    pub fn launched_uris(&self) -> Vec<String> {
+        std::fs::read_to_string(self.launched_file())
+            .map(|contents| contents.lines().map(ToString::to_string).collect())
+            .unwrap_or_default()
+    }
+
+    /// All lines in the activation history of the service.
+    pub fn activations(&self) -> Vec<String> {
+        let path = self
+            .root
+            .join("state")
+            .join("gnome-search-providers-jetbrains")
+            .join("activations");
+        std::fs::read_to_string(path)
+            .map(|contents| contents.lines().map(ToString::to_string).collect())
+            .unwrap_or_default()
+    }
+
+    /// Add recent `projects` to the configuration directory `config_dir` of an IDE.
+    ///
+    /// `config_dir` is relative to `$XDG_CONFIG_HOME`, e.g. `JetBrains/IntelliJIdea2021.3`.
+    /// Create a directory in `~/Code` for every project.
+    pub fn add_recent_projects(&self, config_dir: &str, projects: &[&str]) {
+        let entries: Vec<String> = projects
+            .iter()
+            .map(|project| {
+                std::fs::create_dir_all(self.home().join("Code").join(project)).unwrap();
+                format!(
+                    "        <entry key=\"$USER_HOME$/Code/{}\"><value><RecentProjectMetaInfo/></value></entry>",
+                    project
+                )
+            })
+            .collect();
+        write_file(
+            &self
+                .root
+                .join("config")
+                .join(config_dir)
+                .join("options")
+                .join("recentProjects.xml"),
+            format!(
+                r#"<application>
+  <component name="RecentProjectsManager">
+    <option name="additionalInfo">
+      <map>
+{}
+      </map>
+    </option>
+  </component>
+</application>
+"#,
+                entries.join("\n")
+            ),
+        );
+    }
+
+    /// Write the configuration file of the service.
+    pub fn write_config(&self, contents: &str) {
+        write_file(
+            &self
+                .root
+                .join("config")
+                .join("gnome-search-providers-jetbrains")
+                .join("config.ini"),
+            contents,
+        );
+    }
+
+    /// Start the service, and wait until it acquired its bus name.
+    pub fn start_service(&mut self) {
+        let service = Command::new(env!("CARGO_BIN_EXE_gnome-search-providers-jetbrains"))
+            .env("DBUS_SESSION_BUS_ADDRESS", &self.address)
+            .env("HOME", self.home())
+            .env("XDG_CONFIG_HOME", self.root.join("config"))
+            .env("XDG_DATA_HOME", self.root.join("data"))
+            .env("XDG_DATA_DIRS", self.root.join("data-dirs"))
+            .env("XDG_CACHE_HOME", self.root.join("cache"))
+            .env("XDG_STATE_HOME", self.root.join("state"))
+            .env("RUST_LOG", "debug")
+            .env_remove("JOURNAL_STREAM")
+            .env_remove("NOTIFY_SOCKET")
+            .env_remove("WATCHDOG_USEC")
+            .env_remove("WATCHDOG_PID")
+            .spawn()
+            .unwrap();
+        self.service = Some(service);
+
+        let dbus = zbus::blocking::fdo::DBusProxy::new(&self.connection).unwrap();
+        let acquired = wait_for(|| {
+            if let Some(status) = self.service.as_mut().unwrap().try_wait().unwrap() {
+                panic!("Service exited prematurely: {}", status);
+            }
+            dbus.name_has_owner(BUSNAME.try_into().unwrap()).unwrap()
+        });
+        assert!(acquired, "Service did not acquire {} in time", BUSNAME);
+    }
+
+    /// Get a proxy for the search provider at `path`.
+    pub fn search_provider(&self, path: &str) -> zbus::blocking::Proxy<'_> {
+        zbus::blocking::Proxy::new(
+            &self.connection,
+            BUSNAME,
+            path.to_string(),
+            SEARCH_PROVIDER_INTERFACE,
+        )
+        .unwrap()
+    }
+
+    /// Get a proxy for the log control interface of the service.
+    pub fn log_control(&self) -> zbus::blocking::Proxy<'_> {
+        zbus::blocking::Proxy::new(
+            &self.connection,
+            BUSNAME,
+            "/org/freedesktop/LogControl1",
+            "org.freedesktop.LogControl1",
+        )
+        .unwrap()
+    }
+
+    /// Get a proxy for the scopes of launched IDEs.
+    pub fn launched_scopes(&self) -> zbus::blocking::Proxy<'_> {
+        zbus::blocking::Proxy::new(
+            &self.connection,
+            BUSNAME,
+            "/de/swsnr/searchprovider/jetbrains/scopes",
+            "de.swsnr.searchprovider.LaunchedScopes1",
+        )
+        .unwrap()
+    }
+
+    /// All units started by the stub systemd manager so far.
+    pub fn started_units(&self) -> Vec<StartedUnit> {
+        self.started_units.lock().clone()
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        if let Some(mut service) = self.service.take() {
+            let _ = service.kill();
+            let _ = service.wait();
+        }
+        let _ = self.dbus_daemon.kill();
+        let _ = self.dbus_daemon.wait();
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
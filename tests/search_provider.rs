@@ -0,0 +1,344 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Test the search providers of the service end to end over D-Bus.
+//!
+//! These tests need `dbus-daemon` and fail without it, so they're ignored by default; run them
+//! with `cargo test -- --ignored`.
+
+use std::collections::HashMap;
+
+use pretty_assertions::assert_eq;
+
+use gnome_search_provider_common::zbus;
+use gnome_search_provider_common::zbus::zvariant::{OwnedObjectPath, OwnedValue};
+
+mod harness;
+
+use harness::*;
+
+/// The object path of the search provider for IDEA.
+const IDEA_PATH: &str = "/de/swsnr/searchprovider/jetbrains/toolbox/idea";
+
+/// Start the service with IDEA and some recent projects.
+fn start_with_idea() -> Harness {
+    let mut harness = Harness::new();
+    harness.add_app("jetbrains-idea.desktop", "IntelliJ IDEA");
+    harness.add_recent_projects(
+        "JetBrains/IntelliJIdea2021.3",
+        &["mdcat", "gnome-search-providers-jetbrains"],
+    );
+    harness.write_config("[search]\nload-timeout-ms=0\n");
+    harness.start_service();
+    harness
+}
+
+fn metas(provider: &zbus::blocking::Proxy<'_>, ids: &[String]) -> Vec<HashMap<String, String>> {
+    let metas: Vec<HashMap<String, OwnedValue>> = provider.call("GetResultMetas", &(ids,)).unwrap();
+    metas
+        .into_iter()
+        .map(|meta| {
+            meta.into_iter()
+                .map(|(key, value)| (key, String::try_from(value).unwrap()))
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+#[ignore]
+fn find_recent_projects() {
+    let harness = start_with_idea();
+    let provider = harness.search_provider(IDEA_PATH);
+    let ids: Vec<String> = provider
+        .call("GetInitialResultSet", &(vec!["mdcat"],))
+        .unwrap();
+    assert_eq!(ids.len(), 1);
+
+    let metas = metas(&provider, &ids);
+    assert_eq!(metas.len(), 1);
+    assert_eq!(metas[0]["id"], ids[0]);
+    assert_eq!(metas[0]["name"], "mdcat");
+    assert_eq!(metas[0]["description"], "~/Code/mdcat");
+
+    let refined: Vec<String> = provider
+        .call("GetSubsearchResultSet", &(&ids, vec!["mdcat", "jetbrains"]))
+        .unwrap();
+    assert!(refined.is_empty());
+}
+
+#[test]
+#[ignore]
+fn refine_with_qualified_terms() {
+    let harness = start_with_idea();
+    let provider = harness.search_provider(IDEA_PATH);
+    // Gnome Shell sends every keystroke, so incomplete qualifiers come first
+    let ids: Vec<String> = provider
+        .call("GetInitialResultSet", &(vec!["name:"],))
+        .unwrap();
+    assert!(ids.is_empty());
+    let ids: Vec<String> = provider
+        .call("GetSubsearchResultSet", &(&ids, vec!["name:mdcat"]))
+        .unwrap();
+    assert_eq!(ids.len(), 1);
+    let ids: Vec<String> = provider
+        .call("GetSubsearchResultSet", &(&ids, vec!["name:mdcat", "-"]))
+        .unwrap();
+    assert_eq!(ids.len(), 1);
+}
+
+#[test]
+#[ignore]
+fn activate_result() {
+    let harness = start_with_idea();
+    let provider = harness.search_provider(IDEA_PATH);
+    let ids: Vec<String> = provider
+        .call("GetInitialResultSet", &(vec!["mdcat"],))
+        .unwrap();
+    provider
+        .call::<_, _, ()>("ActivateResult", &(&ids[0], vec!["mdcat"], 0_u32))
+        .unwrap();
+
+    let project = harness.home().join("Code").join("mdcat");
+    assert!(
+        wait_for(|| harness
+            .launched_uris()
+            .iter()
+            .any(|uri| uri.ends_with(project.to_str().unwrap()))),
+        "IDEA not launched with {}: {:?}",
+        project.display(),
+        harness.launched_uris()
+    );
+    assert!(
+        wait_for(|| !harness.started_units().is_empty()),
+        "No scope started for IDEA"
+    );
+    let units = harness.started_units();
+    assert_eq!(units.len(), 1);
+    assert!(
+        units[0]
+            .name
+            .starts_with("app-gnome\\x2dsearch\\x2dproviders\\x2djetbrains-jetbrains\\x2didea-"),
+        "Unexpected scope name {}",
+        units[0].name
+    );
+    assert!(units[0].name.ends_with(".scope"));
+    assert!(units[0].properties.contains_key("PIDs"));
+
+    let activations = harness.activations();
+    assert_eq!(activations.len(), 1);
+    assert!(activations[0].ends_with(&format!("\t{}\tmdcat", ids[0])));
+}
+
+#[test]
+#[ignore]
+fn activate_unknown_result() {
+    let harness = start_with_idea();
+    let provider = harness.search_provider(IDEA_PATH);
+    let result = provider.call::<_, _, ()>("ActivateResult", &("foo", vec!["mdcat"], 0_u32));
+    assert!(result.is_err());
+    assert!(harness.launched_uris().is_empty());
+    assert!(harness.activations().is_empty());
+}
+
+#[test]
+#[ignore]
+fn only_serve_installed_apps() {
+    let harness = start_with_idea();
+    let provider = harness.search_provider("/de/swsnr/searchprovider/jetbrains/toolbox/clion");
+    let result: zbus::Result<Vec<String>> = provider.call("GetInitialResultSet", &(vec!["mdcat"],));
+    assert!(result.is_err());
+}
+
+#[test]
+#[ignore]
+fn log_control() {
+    let harness = start_with_idea();
+    let log_control = harness.log_control();
+    log_control.set_property("LogLevel", "notice").unwrap();
+    assert_eq!(
+        log_control.get_property::<String>("LogLevel").unwrap(),
+        "notice"
+    );
+    log_control.set_property("LogTarget", "null").unwrap();
+    assert_eq!(
+        log_control.get_property::<String>("LogTarget").unwrap(),
+        "null"
+    );
+    assert_eq!(
+        log_control
+            .get_property::<String>("SyslogIdentifier")
+            .unwrap(),
+        "gnome-search-providers-jetbrains"
+    );
+}
+
+/// Activating a project which is open in a scope launches the IDE again.
+///
+/// The service can't raise the window of the IDE, so without a command line launcher it relies
+/// on the IDE to hand the project to its running instance.
+#[test]
+#[ignore]
+fn launch_open_project_again() {
+    let harness = start_with_idea();
+    let provider = harness.search_provider(IDEA_PATH);
+    let ids: Vec<String> = provider
+        .call("GetInitialResultSet", &(vec!["mdcat"],))
+        .unwrap();
+    provider
+        .call::<_, _, ()>("ActivateResult", &(&ids[0], vec!["mdcat"], 0_u32))
+        .unwrap();
+
+    let scopes = harness.launched_scopes();
+    let project = harness.home().join("Code").join("mdcat");
+    let project = project.to_str().unwrap();
+    assert!(
+        wait_for(|| scopes
+            .call::<_, _, bool>("IsRunning", &("jetbrains-idea.desktop", project))
+            .unwrap()),
+        "Project {} not running in a scope",
+        project
+    );
+    let listed: Vec<(String, String, String, String, OwnedObjectPath)> =
+        scopes.call("ListScopes", &()).unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].0, harness.started_units()[0].name);
+    assert_eq!(listed[0].1, "jetbrains-idea.desktop");
+    assert_eq!(listed[0].2, project);
+    assert_eq!(listed[0].3, "active");
+
+    assert_eq!(
+        metas(&provider, &ids)[0]["description"],
+        "~/Code/mdcat (open)"
+    );
+
+    provider
+        .call::<_, _, ()>("ActivateResult", &(&ids[0], vec!["mdcat"], 0_u32))
+        .unwrap();
+    assert!(
+        wait_for(|| harness.launched_uris().len() == 2),
+        "Project not handed to IDEA again: {:?}",
+        harness.launched_uris()
+    );
+    // The new process may start another instance instead of handing the project over, so it
+    // must get a scope as well.
+    assert!(
+        wait_for(|| harness.started_units().len() == 2),
+        "No scope started for second launch of IDEA"
+    );
+}
+
+#[test]
+#[ignore]
+fn open_in_running_ide() {
+    let mut harness = Harness::new();
+    harness.add_app("jetbrains-idea.desktop", "IntelliJ IDEA");
+    harness.add_recent_projects("JetBrains/IntelliJIdea2021.3", &["mdcat"]);
+    let cli = harness.add_cli("idea");
+    harness.write_config(&format!(
+        "[search]\nload-timeout-ms=0\n\n[provider:jetbrains-idea.desktop]\ncli={}\n",
+        cli.display()
+    ));
+    harness.run_ide("JetBrains/IntelliJIdea2021.3");
+    harness.start_service();
+
+    let provider = harness.search_provider(IDEA_PATH);
+    let ids: Vec<String> = provider
+        .call("GetInitialResultSet", &(vec!["mdcat"],))
+        .unwrap();
+    provider
+        .call::<_, _, ()>("ActivateResult", &(&ids[0], vec!["mdcat"], 0_u32))
+        .unwrap();
+
+    // The service replies once the launcher is in its scope, without waiting for it to exit.
+    let project = harness.home().join("Code").join("mdcat");
+    assert!(
+        wait_for(|| !harness.launched_uris().is_empty()),
+        "Command line launcher not run"
+    );
+    assert_eq!(
+        harness.launched_uris(),
+        vec![format!("cli:{}", project.display())]
+    );
+    // The launcher might start a new instance, so it runs in a scope of the IDE.
+    assert!(
+        wait_for(|| !harness.started_units().is_empty()),
+        "No scope started for command line launcher"
+    );
+    let units = harness.started_units();
+    assert_eq!(units.len(), 1);
+    assert!(
+        units[0]
+            .name
+            .starts_with("app-gnome\\x2dsearch\\x2dproviders\\x2djetbrains-jetbrains\\x2didea-"),
+        "Unexpected scope name {}",
+        units[0].name
+    );
+}
+
+#[test]
+#[ignore]
+fn fail_if_running_ide_has_no_cli() {
+    let mut harness = Harness::new();
+    harness.add_app("jetbrains-idea.desktop", "IntelliJ IDEA");
+    harness.add_recent_projects("JetBrains/IntelliJIdea2021.3", &["mdcat"]);
+    let cli = harness.home().join("bin").join("idea");
+    harness.write_config(&format!(
+        "[search]\nload-timeout-ms=0\n\n[provider:jetbrains-idea.desktop]\ncli={}\n",
+        cli.display()
+    ));
+    harness.run_ide("JetBrains/IntelliJIdea2021.3");
+    harness.start_service();
+
+    let provider = harness.search_provider(IDEA_PATH);
+    let ids: Vec<String> = provider
+        .call("GetInitialResultSet", &(vec!["mdcat"],))
+        .unwrap();
+    let result = provider.call::<_, _, ()>("ActivateResult", &(&ids[0], vec!["mdcat"], 0_u32));
+    assert!(result.is_err(), "Activation succeeded without launcher");
+    // Launching the IDE again wouldn't open the project in the running instance either
+    assert!(harness.launched_uris().is_empty());
+    assert!(harness.started_units().is_empty());
+    assert!(harness.activations().is_empty());
+}
+
+#[test]
+#[ignore]
+fn launch_with_env_and_args() {
+    let mut harness = Harness::new();
+    harness.add_app("jetbrains-idea.desktop", "IntelliJ IDEA");
+    harness.add_recent_projects("JetBrains/IntelliJIdea2021.3", &["mdcat"]);
+    harness.write_config(
+        "[search]\nload-timeout-ms=0\n\n[launch]\nenv=HARNESS_LAUNCH_PREFIX=env:\n\n[provider:jetbrains-idea.desktop]\nargs=nosplash;a b\n",
+    );
+    harness.start_service();
+
+    let provider = harness.search_provider(IDEA_PATH);
+    let ids: Vec<String> = provider
+        .call("GetInitialResultSet", &(vec!["mdcat"],))
+        .unwrap();
+    provider
+        .call::<_, _, ()>("ActivateResult", &(&ids[0], vec!["mdcat"], 0_u32))
+        .unwrap();
+
+    let project = harness.home().join("Code").join("mdcat");
+    assert!(
+        wait_for(|| !harness.launched_uris().is_empty()),
+        "IDEA not launched"
+    );
+    let launched = harness.launched_uris();
+    assert_eq!(launched.len(), 1);
+    assert!(
+        launched[0].starts_with("env:nosplash a b ")
+            && launched[0].ends_with(project.to_str().unwrap()),
+        "IDEA launched without environment or arguments: {}",
+        launched[0]
+    );
+    assert!(
+        wait_for(|| !harness.started_units().is_empty()),
+        "No scope started for IDEA"
+    );
+}